@@ -1,19 +1,23 @@
 mod min;
 
-use std::{collections::HashMap, fs, io::{stdout, ErrorKind, Write}, sync::{Arc, Mutex}, time::Duration};
-use anyhow::Result;
+use std::{collections::{BTreeMap, HashMap, HashSet, VecDeque}, fmt::Display, fs, io::{stdin, stdout, IsTerminal, Write}, panic::AssertUnwindSafe, path::{Path, PathBuf}, pin::Pin, net::{Ipv4Addr, SocketAddr, SocketAddrV4}, process::ExitCode, sync::{atomic::{AtomicBool, AtomicU16, AtomicU64, AtomicUsize, Ordering}, Arc, Mutex}, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
+use anyhow::{bail, Result};
+use chrono::{Local, TimeZone};
 use clap::Parser;
-use crossterm::{cursor::MoveTo, event::{DisableMouseCapture, EnableMouseCapture, Event::{Key, Mouse, Resize}, EventStream, KeyCode, MouseEventKind}, execute, style::Stylize, terminal::{disable_raw_mode, enable_raw_mode, is_raw_mode_enabled, size, EnterAlternateScreen, LeaveAlternateScreen}};
-use futures_lite::StreamExt;
-use iroh::{discovery::static_provider::StaticProvider, protocol::Router, Endpoint, NodeAddr, NodeId, PublicKey, SecretKey};
-use iroh_gossip::{net::Gossip, api::{Event, GossipReceiver}, proto::TopicId};
+use crossterm::{cursor::MoveTo, event::{DisableMouseCapture, EnableMouseCapture, Event::{Key, Mouse, Resize}, EventStream, KeyCode, MouseButton, MouseEventKind}, execute, style::Stylize, terminal::{disable_raw_mode, enable_raw_mode, is_raw_mode_enabled, size, EnterAlternateScreen, LeaveAlternateScreen}};
+use futures_lite::{FutureExt, StreamExt};
+use iroh::{discovery::static_provider::StaticProvider, protocol::Router, Endpoint, NodeAddr, NodeId, PublicKey, RelayMode, RelayUrl, SecretKey};
+use iroh_base::Signature;
+use iroh_gossip::{net::Gossip, api::{Event, GossipReceiver, GossipSender}, proto::{HyparviewConfig, TopicId}};
 use serde::{Deserialize, Serialize};
+use min::GameConfig;
 
 /// Chat over iroh-gossip
 ///
-/// This broadcasts unsigned messages over iroh-gossip.
+/// This broadcasts messages over iroh-gossip, each signed by the node that wrote it.
 ///
-/// By default a new node id is created when starting the example.
+/// Joining keeps the same node id from one run to the next, its key is saved in the config file.
+/// Hosting uses the room's own host key instead.
 ///
 /// By default, we use the default n0 discovery services to dial by `NodeId`.
 #[derive(Parser, Debug)]
@@ -24,21 +28,496 @@ struct Args {
     /// Set the bind port for our socket. By default, a random port will be used.
     #[clap(short, long, default_value = "0")]
     bind_port: u16,
+    /// Print chat events as one JSON object per line instead of styled text, for bots and scripts.
+    #[clap(long)]
+    json: bool,
+    /// Start games with this many bits instead of the usual amount. Only used if your opponent picked the same.
+    #[clap(long)]
+    game_bits: Option<i32>,
+    /// Start games with this many colors in the vbox. Only used if your opponent picked the same.
+    #[clap(long, value_parser = clap::value_parser!(u64).range(1..=min::MAX_COLORS as u64))]
+    game_colors: Option<u64>,
+    /// Start games with this many skills in the vbox. Only used if your opponent picked the same.
+    #[clap(long, value_parser = clap::value_parser!(u64).range(1..=min::MAX_SKILLS as u64))]
+    game_skills: Option<u64>,
+    /// Leave the room after this many seconds without typing anything, like /quit. 0 means never.
+    #[clap(long, default_value_t = 0)]
+    idle_timeout: u64,
+    /// When hosting, replay this many recent messages to people who join so they aren't staring at nothing. 0 turns it off.
+    #[clap(long, default_value_t = 20)]
+    backlog: usize,
+    /// When opening a room, put the command for joining it on the clipboard, same as /copy.
+    #[clap(long)]
+    copy: bool,
+    /// Print every message exactly as it arrived, before it's parsed, to stderr. For chasing down protocol problems.
+    #[clap(long)]
+    dump_raw: bool,
+    /// Append connection events (peers coming and going, rejoins, errors) to this file, for looking back on
+    /// what a long running host went through. It's moved to <path>.1 once it gets past a megabyte.
+    #[clap(long, value_name = "PATH")]
+    event_log: Option<PathBuf>,
+    /// Append a plain text transcript of the chat to this file, with the time in front of every line.
+    #[clap(long, value_name = "PATH")]
+    log: Option<PathBuf>,
+    /// Send messages in a compact binary encoding instead of json, for busy rooms. Everyone has to pick
+    /// this to see each other, rooms with and without it are kept apart.
+    #[clap(long)]
+    compact: bool,
+    /// Gossip tuning: how many peers to stay directly connected to (1-64, library default 5). More is sturdier
+    /// when people drop out and spreads messages in fewer hops, but every message is sent over more connections.
+    #[clap(long, value_parser = clap::value_parser!(u64).range(1..=64))]
+    gossip_active_view: Option<u64>,
+    /// Gossip tuning: how many spare peers to remember for replacing dropped ones (1-1024, library default 30).
+    /// Bigger rooms heal faster with more, at the cost of a little memory and background chatter.
+    #[clap(long, value_parser = clap::value_parser!(u64).range(1..=1024))]
+    gossip_passive_view: Option<u64>,
+    /// Gossip tuning: the largest message in bytes that will be sent or accepted (512-1048576, library default 4096).
+    /// Everyone in a room should use the same value, bigger messages from others are dropped.
+    #[clap(long, value_parser = clap::value_parser!(u64).range(512..=1048576))]
+    gossip_max_message_size: Option<u64>,
+    /// With `open`, how many times to try setting up the room before giving up. Waits a bit longer between each.
+    #[clap(long, default_value = "3", value_parser = clap::value_parser!(u32).range(1..=10))]
+    host_attempts: u32,
+    /// How many seconds to wait for the network, and for the host when joining, before giving up.
+    #[clap(long, default_value = "10", value_parser = clap::value_parser!(u64).range(1..=600))]
+    timeout: u64,
+    /// With `open`, only let this node id into the room. Can be given more than once. Everyone else gets
+    /// kicked as soon as they show up, which like /kick only works on clients that play along.
+    #[clap(long = "allow", value_name = "NODE_ID")]
+    allow: Vec<PublicKey>,
+    /// Go straight into the game queue once someone else is around, like typing /min. If somebody is
+    /// already waiting you get matched with them.
+    #[clap(long)]
+    quickplay: bool,
+    /// Ring the terminal bell when someone else sends a message, at most once every few seconds.
+    /// Ignored with --json and --quiet.
+    #[clap(long)]
+    bell: bool,
+    /// Don't touch the network at all. Chat goes nowhere and /min starts a practice game, handy for working on the UI.
+    #[clap(long)]
+    offline: bool,
+    /// Only talk to other minimal processes on this computer, over localhost with no relays or discovery.
+    /// Run `minimal --local open` in one terminal and `minimal --local join` in another, with the same --room if any.
+    #[clap(long, conflicts_with = "offline")]
+    local: bool,
+    /// Chat in a named room instead of the default one. Use `@name` to go to a room saved with /bookmark.
+    /// The name goes into the room's topic and host key, so each room has its own host and nobody needs a ticket
+    /// to find it. Leaving it empty is the same default room clients used before rooms existed.
+    #[clap(short, long, default_value = "")]
+    room: String,
+    /// Only people with this password can find the room or host it. It goes into the topic and host key, so
+    /// everyone in the room has to use the same one, and a ticket won't work without it.
+    #[clap(long, env = "MINIMAL_PASSWORD", hide_env_values = true)]
+    password: Option<String>,
+    /// Use this config file. By default minconfig.json in the current directory is used if there is one,
+    /// otherwise minimal/minconfig.json in your user config directory.
+    #[clap(long)]
+    config: Option<PathBuf>,
+    /// Keep a separate nickname, key and bookmarks under this name, in minconfig.<name>.json.
+    /// `--config` still wins if both are given.
+    #[clap(long, value_parser = parse_profile)]
+    profile: Option<String>,
+    /// Print extra details about what minimal is doing, like which config file it picked.
+    #[clap(short, long)]
+    verbose: bool,
+    /// With `open`, don't take the room's host key. Joins through whoever already holds it instead,
+    /// with a fresh identity, for when someone else is already hosting.
+    #[clap(long)]
+    new_identity: bool,
+    /// Don't print a line every time someone connects or disconnects, and don't ring the bell. /list still
+    /// shows the count.
+    #[clap(short, long)]
+    quiet: bool,
     #[clap(subcommand)]
     command: Command,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 struct MinConfig {
+    #[serde(default)]
     name: String,
+    #[serde(default)]
+    bookmarks: BTreeMap<String, Bookmark>,
+    // how many recent messages are remembered so reactions can point back to them, and for /history.
+    // each one is roughly its author and text, so even a few thousand only costs a few hundred KB
+    #[serde(default = "default_history_limit")]
+    history_limit: usize,
+    // what commands start with, for keyboards where `/` is awkward or people who talk about paths a lot
+    #[serde(default = "default_command_prefix")]
+    command_prefix: String,
+    // whether chat lines start with the time, toggled with /timestamps
+    #[serde(default = "default_timestamps")]
+    timestamps: bool,
+    // "24h" or "12h"
+    #[serde(default)]
+    time_format: TimeFormat,
+    // the most of the terminal a game takes up, it's centered in whatever is left over. unset fills it all
+    #[serde(default)]
+    max_play_cols: Option<u16>,
+    #[serde(default)]
+    max_play_rows: Option<u16>,
+    // the key `join` uses, in hex, so we're the same node id to everyone from one run to the next.
+    // written the first time there isn't one, and replaced by /rekey
+    #[serde(default)]
+    secret_key: Option<String>,
+    // where this was loaded from, so saving goes back to the same file
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+enum TimeFormat {
+    #[default]
+    #[serde(rename = "24h")]
+    TwentyFourHour,
+    #[serde(rename = "12h")]
+    TwelveHour,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Bookmark {
+    room: String,
+}
+
+const CONFIG_FILE: &str = "minconfig.json";
+const DEFAULT_HISTORY_LIMIT: usize = 500;
+
+fn default_history_limit() -> usize {
+    DEFAULT_HISTORY_LIMIT
+}
+
+fn default_command_prefix() -> String {
+    "/".to_string()
+}
+
+fn default_timestamps() -> bool {
+    true
+}
+
+impl Default for MinConfig {
+    fn default() -> Self {
+        MinConfig {
+            name: String::new(),
+            bookmarks: BTreeMap::new(),
+            history_limit: DEFAULT_HISTORY_LIMIT,
+            command_prefix: default_command_prefix(),
+            timestamps: default_timestamps(),
+            time_format: TimeFormat::default(),
+            max_play_cols: None,
+            max_play_rows: None,
+            secret_key: None,
+            path: PathBuf::new(),
+        }
+    }
+}
+
+// the platform's per-user config directory, worked out the same way the usual crates do it
+fn user_config_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        return Some(dir.into());
+    }
+    if cfg!(windows) {
+        return std::env::var_os("APPDATA").map(PathBuf::from);
+    }
+    let home = PathBuf::from(std::env::var_os("HOME")?);
+    Some(if cfg!(target_os = "macos") { home.join("Library/Application Support") } else { home.join(".config") })
+}
+
+// profile names end up in a file name, so nothing that could climb out of the directory
+fn parse_profile(name: &str) -> Result<String, String> {
+    if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        Ok(name.to_string())
+    } else {
+        Err("profile names can only use letters, numbers, - and _".to_string())
+    }
+}
+
+// `--config` wins, then `--profile` picks minconfig.<name>.json instead of minconfig.json. either file is
+// looked for in the current directory first (how it always worked), then the user config dir
+fn config_path(explicit: Option<PathBuf>, profile: Option<&str>) -> PathBuf {
+    if let Some(path) = explicit {
+        return path;
+    }
+    let file = match profile {
+        Some(profile) => format!("minconfig.{profile}.json"),
+        None => CONFIG_FILE.to_string(),
+    };
+    if Path::new(&file).exists() {
+        return file.into();
+    }
+    user_config_dir().map_or_else(|| file.clone().into(), |dir| dir.join("minimal").join(&file))
+}
+
+impl MinConfig {
+    // read from `path` if it exists, otherwise it will need to be created
+    fn load(path: PathBuf) -> Result<Self> {
+        if let Some(minconfig) = Self::read(&path)? {
+            return Ok(minconfig);
+        }
+        status(format!("> {} is missing or empty, creating a new one", path.display()).yellow());
+        let minconfig = Self { path, ..Self::default() };
+        minconfig.save()?;
+        Ok(minconfig)
+    }
+    // what's in `path`, without writing anything. None if it's missing, or empty (e.g. from
+    // `touch minconfig.json`), which isn't valid json but is no reason to stop
+    fn read(path: &Path) -> Result<Option<Self>> {
+        let unreadable = |e: std::io::Error| MinimalError::Config(format!("couldn't read {}: {e}", path.display()));
+        let contents = if fs::exists(path).map_err(unreadable)? { fs::read_to_string(path).map_err(unreadable)? } else { String::new() };
+        if contents.trim().is_empty() {
+            return Ok(None);
+        }
+        match serde_json::from_str(&contents) {
+            Ok(minconfig) => Ok(Some(Self { path: path.to_path_buf(), ..minconfig })),
+            Err(e) => Err(MinimalError::Config(format!("{} isn't a valid config: {e}", path.display())).into()),
+        }
+    }
+    // the saved identity, or a new one that gets saved for next time. a key that doesn't decode is
+    // replaced rather than stopping anyone from joining
+    fn identity(&mut self) -> SecretKey {
+        let saved = self.secret_key.as_deref()
+            .and_then(|hex| data_encoding::HEXLOWER_PERMISSIVE.decode(hex.trim().as_bytes()).ok())
+            .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok());
+        if let Some(bytes) = saved {
+            return SecretKey::from_bytes(&bytes);
+        }
+        if self.secret_key.as_deref().is_some_and(|hex| !hex.trim().is_empty()) {
+            status(format!("> the secret_key in {} isn't a valid key, making a new one", self.path.display()).yellow());
+        }
+        let secret_key = SecretKey::generate(&mut rand::rng());
+        self.remember_identity(&secret_key);
+        secret_key
+    }
+
+    fn remember_identity(&mut self, secret_key: &SecretKey) {
+        self.secret_key = Some(data_encoding::HEXLOWER.encode(&secret_key.to_bytes()));
+        if let Err(e) = self.save() {
+            status(format!("> couldn't save your identity to {} ({e}), you'll get a new one next time", self.path.display()).yellow());
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        // the user config dir might not have a minimal folder yet
+        if let Some(parent) = self.path.parent() && !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+// held while minimal is running so a second copy using the same config can tell. it's only a warning,
+// two copies work fine, they just keep overwriting each other's settings and stats. it's an advisory
+// lock on a file next to the config, which the OS lets go of however we exit, crashes and kill -9
+// included, so there's never a stale one to clean up
+struct ConfigLock {
+    _file: fs::File,
+}
+
+impl ConfigLock {
+    // None if someone else already holds it, after telling the user so
+    fn acquire(config_path: &Path) -> Option<Self> {
+        let path = config_path.with_extension("lock");
+        if let Some(parent) = path.parent() && !parent.as_os_str().is_empty() {
+            let _ = fs::create_dir_all(parent);
+        }
+        // can't write next to the config, which saving will complain about soon enough anyway
+        let Ok(mut file) = fs::OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&path) else { return None };
+        match file.try_lock() {
+            Ok(()) => {
+                // the pid is only there for the warning the next copy prints
+                let _ = file.set_len(0);
+                let _ = write!(file, "{}", std::process::id());
+                Some(ConfigLock { _file: file })
+            }
+            Err(fs::TryLockError::WouldBlock) => {
+                // some systems won't let us read a file someone else has locked, then there's no pid to show
+                let pid = fs::read_to_string(&path).ok().filter(|pid| !pid.trim().is_empty());
+                let who = pid.map_or("another minimal".to_string(), |pid| format!("another minimal (pid {})", pid.trim()));
+                status(format!("> {who} is using {} already", config_path.display()).yellow());
+                status("> settings and stats will get mixed up between the two, use --config for a separate one".yellow());
+                None
+            }
+            // a filesystem without locks, nothing to be done about it
+            Err(fs::TryLockError::Error(_)) => None,
+        }
+    }
+}
+
+// game results, kept next to the config file
+const STATS_FILE: &str = "minstats.json";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Record {
+    wins: u32,
+    losses: u32,
+    aborts: u32,
+}
+
+// records by node id, which stays the same between runs and can't be taken by someone picking the same
+// nickname. names only come into it when the records are shown
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Stats {
+    #[serde(default)]
+    nodes: BTreeMap<NodeId, Record>,
+}
+
+impl Stats {
+    // no file yet, or an empty one, just means no games yet
+    fn load(path: &Path) -> Result<Self> {
+        if !fs::exists(path)? {
+            return Ok(Self::default());
+        }
+        let contents = fs::read_to_string(path)?;
+        if contents.trim().is_empty() {
+            return Ok(Self::default());
+        }
+        Ok(serde_json::from_str(&contents)?)
+    }
+    fn record(path: &Path, id: NodeId, outcome: GameOutcome) -> Result<()> {
+        let mut stats = Self::load(path)?;
+        let record = stats.nodes.entry(id).or_default();
+        match outcome {
+            GameOutcome::Won => record.wins += 1,
+            GameOutcome::Lost => record.losses += 1,
+            GameOutcome::Aborted => record.aborts += 1,
+        }
+        fs::write(path, serde_json::to_string_pretty(&stats)?)?;
+        Ok(())
+    }
+}
+
+// a typed line is either a command or chat
+enum Input {
+    Command(String),
+    Chat(String),
+}
+
+// the one place that knows about the command prefix. commands come back spelled with `/` whatever the
+// prefix is, so nothing after this has to care. doubling the prefix sends it as a literal, `//etc` says `/etc`
+fn parse_input(line: &str, prefix: &str) -> Input {
+    match line.strip_prefix(prefix) {
+        Some(rest) if rest.starts_with(prefix) => Input::Chat(rest.to_string()),
+        Some(rest) => Input::Command(format!("/{rest}")),
+        None => Input::Chat(line.to_string()),
+    }
+}
+
+// what a command takes after its name. /help shows it, and a command that needs something isn't run
+// without it
+#[derive(Debug, Clone, Copy)]
+enum Params {
+    Nothing,
+    Required(&'static str),
+    Optional(&'static str),
+}
+
+impl Params {
+    fn usage(self) -> &'static str {
+        match self {
+            Params::Nothing => "",
+            Params::Required(usage) | Params::Optional(usage) => usage,
+        }
+    }
+}
+
+// a command running, with the session borrowed until it's done
+type CommandFuture<'a> = Pin<Box<dyn Future<Output = Result<()>> + 'a>>;
+
+struct SlashCommand {
+    name: &'static str,
+    params: Params,
+    help: &'static str,
+    run: for<'a> fn(&'a mut Session, Call<'a>) -> CommandFuture<'a>,
+}
+
+impl SlashCommand {
+    // how to type it, with the prefix actually in use, which isn't always `/`
+    fn usage(&self, prefix: &str) -> String {
+        format!("{prefix}{} {}", &self.name[1..], self.params.usage()).trim_end().to_string()
+    }
+}
+
+// one command as it was typed: which one, and the words after its name
+struct Call<'a> {
+    command: &'static SlashCommand,
+    args: &'a [&'a str],
+}
+
+impl Call<'_> {
+    // everything after the name, spaced the way it was typed
+    fn rest(&self) -> String {
+        self.args.join(" ")
+    }
+}
+
+// every slash command there is, with what it takes and the `Session` method that runs it. /help is just
+// this table printed out
+const COMMANDS: &[SlashCommand] = &[
+    SlashCommand { name: "/help", params: Params::Nothing, help: "list the commands", run: |session, call| Box::pin(session.help(call)) },
+    SlashCommand { name: "/settings", params: Params::Nothing, help: "show what's turned on and what the config says", run: |session, call| Box::pin(session.settings(call)) },
+    SlashCommand { name: "/nick", params: Params::Required("<name>"), help: "change your nickname", run: |session, call| Box::pin(session.nick(call)) },
+    SlashCommand { name: "/me", params: Params::Required("<action>"), help: "say what you're doing, like `* you wave`", run: |session, call| Box::pin(session.me(call)) },
+    SlashCommand { name: "/list", params: Params::Nothing, help: "show who is in the room", run: |session, call| Box::pin(session.list(call)) },
+    SlashCommand { name: "/history", params: Params::Optional("[count]"), help: "show earlier messages again, all of them or the last few", run: |session, call| Box::pin(session.history(call)) },
+    SlashCommand { name: "/timestamps", params: Params::Required("on|off"), help: "show or hide the time in front of messages", run: |session, call| Box::pin(session.timestamps(call)) },
+    SlashCommand { name: "/react", params: Params::Required("<emoji>"), help: "react to the last message", run: |session, call| Box::pin(session.react(call)) },
+    SlashCommand { name: "/edit", params: Params::Required("<new text>"), help: "change your last message", run: |session, call| Box::pin(session.edit(call)) },
+    SlashCommand { name: "/delete", params: Params::Nothing, help: "take back your last message", run: |session, call| Box::pin(session.delete(call)) },
+    SlashCommand { name: "/join", params: Params::Optional("[room]"), help: "leave this room for another one", run: |session, call| Box::pin(session.join(call)) },
+    SlashCommand { name: "/rejoin", params: Params::Nothing, help: "reconnect to this room if nothing is coming through", run: |session, call| Box::pin(session.rejoin(call)) },
+    SlashCommand { name: "/bookmark", params: Params::Required("<name>"), help: "save this room to come back to with --room @name", run: |session, call| Box::pin(session.bookmark(call)) },
+    SlashCommand { name: "/copy", params: Params::Nothing, help: "copy the command to join this room", run: |session, call| Box::pin(session.copy(call)) },
+    SlashCommand { name: "/room", params: Params::Nothing, help: "show this room and its gossip topic", run: |session, call| Box::pin(session.show_room(call)) },
+    SlashCommand { name: "/relay", params: Params::Nothing, help: "show which relay your traffic goes through", run: |session, call| Box::pin(session.relay(call)) },
+    SlashCommand { name: "/share", params: Params::Required("<file>"), help: "tell the room about a file by its blake3 hash", run: |session, call| Box::pin(session.share(call)) },
+    SlashCommand { name: "/min", params: Params::Optional("[solo|cancel]"), help: "queue for a game against someone, play the bot, or leave the queue", run: |session, call| Box::pin(session.min(call)) },
+    SlashCommand { name: "/games", params: Params::Nothing, help: "show the games being played", run: |session, call| Box::pin(session.games(call)) },
+    SlashCommand { name: "/spectate", params: Params::Required("<id>"), help: "watch one of the games from /games", run: |session, call| Box::pin(session.spectate(call)) },
+    SlashCommand { name: "/stats", params: Params::Nothing, help: "show your wins and losses", run: |session, call| Box::pin(session.stats(call)) },
+    SlashCommand { name: "/settopic", params: Params::Required("<text>"), help: "set the line joiners see about this room, moderator only", run: |session, call| Box::pin(session.set_topic(call)) },
+    SlashCommand { name: "/kick", params: Params::Required("<name>"), help: "remove someone from the room, moderator only", run: |session, call| Box::pin(session.kick(call)) },
+    SlashCommand { name: "/rekey", params: Params::Nothing, help: "switch to a fresh identity", run: |session, call| Box::pin(session.rekey(call)) },
+    SlashCommand { name: "/version", params: Params::Nothing, help: "show the version and who you are", run: |session, call| Box::pin(session.version(call)) },
+    SlashCommand { name: "/quit", params: Params::Nothing, help: "leave", run: |session, call| Box::pin(session.quit(call)) },
+];
+
+fn find_command(name: &str) -> Option<&'static SlashCommand> {
+    COMMANDS.iter().find(|command| command.name == name)
+}
+
+fn show_help(prefix: &str) {
+    for command in COMMANDS {
+        status(format!("> {:<20} {}", command.usage(prefix), command.help).blue());
+    }
+}
+
+// turn the `--room` argument into an actual room name, looking up `@bookmarks` in the config
+fn resolve_room(room: &str, minconfig: &MinConfig) -> Result<String> {
+    match room.strip_prefix('@') {
+        Some(label) => match minconfig.bookmarks.get(label) {
+            Some(bookmark) => Ok(bookmark.room.clone()),
+            None => Err(MinimalError::Room(format!("there is no bookmark called `{label}` in {}, save one with {}bookmark first", minconfig.path.display(), minconfig.command_prefix)).into()),
+        },
+        None => Ok(room.to_string()),
+    }
+}
+
+fn describe_room(room: &str) -> String {
+    if room.is_empty() { "the default room".to_string() } else { format!("room `{room}`") }
 }
 
 #[derive(Parser, Debug)]
 enum Command {
     /// Open a chat room for a topic and print a ticket for others to join.
     Open,
-    /// Join a chat room from a ticket.
-    Join,
+    /// Join a chat room, from a ticket if the host gave you one.
+    Join {
+        /// What `open` printed. Says which room to join and how to reach its host, so there's no need for --room.
+        ticket: Option<String>,
+    },
+    /// Check that we can reach the network and the chat room, then exit.
+    Doctor,
 }
 
 fn bytes_from_str(s: &str) -> [u8; 32] {
@@ -47,294 +526,2653 @@ fn bytes_from_str(s: &str) -> [u8; 32] {
     let len = bytes.len();
 
     if len > 32 {
-        // Handle cases where the string is too long
-        // For this example, we'll just copy the first 32 bytes.
-        result.copy_from_slice(&bytes[..32]);
-    } else {
-        result[..len].copy_from_slice(bytes);
+        // cutting it off at 32 bytes would put every room whose name only differs past that point on the
+        // same topic and host key, and the header and version alone are most of the 32. hashing keeps
+        // them apart. anything short enough to fit still comes out as before, so the default room doesn't move
+        return *blake3::hash(bytes).as_bytes();
     }
+    result[..len].copy_from_slice(bytes);
     result
 }
 
-const MINIMAL_VERSION: &str = "0.5.0"; // minimal's version, should be consistent with Cargo.toml
+const MINIMAL_VERSION: &str = "0.6.0"; // minimal's version, should be consistent with Cargo.toml
 const MINIMAL_TOPIC_HEADER: &str = "the-rivulet/minimal/topic/"; // prefix for topics
 const MINIMAL_HOST_KEY_KEADER: &str = "the-rivulet/minimal/host/"; // prefix for secret keys
-const CONNECTION_TIMEOUT_SECS: u64 = 10; // seconds to wait before assuming network issue
+const IDLE_WARNING: Duration = Duration::from_secs(10); // how long before `--idle-timeout` kicks in that we warn about it
+
+// in `--json` mode stdout belongs to whatever is reading it, so the friendly status lines are dropped
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+// set by `--bell`, never on in `--json` mode since a stray \x07 would just be noise for whatever reads stdout
+static BELL: AtomicBool = AtomicBool::new(false);
+const BELL_COOLDOWN: Duration = Duration::from_secs(5); // a busy room shouldn't turn into a constant beeping
+
+// set by `--dump-raw`
+static DUMP_RAW: AtomicBool = AtomicBool::new(false);
+// `--timeout`, seconds to wait before assuming a network issue. set once at startup
+static CONNECTION_TIMEOUT_SECS: AtomicU64 = AtomicU64::new(10);
+
+fn connection_timeout() -> u64 {
+    CONNECTION_TIMEOUT_SECS.load(Ordering::Relaxed)
+}
+
+// the biggest message we send or read, signature and all. it's iroh-gossip's own default limit, so it's the
+// most any room can count on. a bigger `--gossip-max-message-size` raises it for everyone using that too
+const MAX_MESSAGE_BYTES: usize = 4096;
+static MESSAGE_LIMIT: AtomicUsize = AtomicUsize::new(MAX_MESSAGE_BYTES);
+
+fn message_limit() -> usize {
+    MESSAGE_LIMIT.load(Ordering::Relaxed)
+}
+
+// set by `--compact`, messages go out as postcard instead of json. set once at startup, before any topic
+// or host key is worked out
+static COMPACT: AtomicBool = AtomicBool::new(false);
+
+// from `--password`, stretched into a key that gets mixed into topics and host keys. without one, anybody
+// who knows the room name can work out both, and hosting the room is as easy as running `open`.
+// set once at startup like `COMPACT`
+static ROOM_KEY: Mutex<Option<[u8; 32]>> = Mutex::new(None);
+
+fn room_key() -> Option<[u8; 32]> {
+    *ROOM_KEY.lock().expect("should be able to acquire lock")
+}
+
+// set by `--quiet`, for busy rooms where the peer count going up and down is just noise
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+// from the config, and flipped at runtime by /timestamps
+static TIMESTAMPS: AtomicBool = AtomicBool::new(true);
+// `time_format` from the config, set once at startup
+static TWELVE_HOUR: AtomicBool = AtomicBool::new(false);
+// `max_play_cols` and `max_play_rows` from the config, 0 for no limit. set once at startup
+static MAX_PLAY_COLS: AtomicU16 = AtomicU16::new(0);
+static MAX_PLAY_ROWS: AtomicU16 = AtomicU16::new(0);
+
+// `[HH:MM:SS] ` (or `[h:MM:SS pm] `) in local time to go in front of a chat line, or nothing if timestamps are off.
+// 0 is what entries from older hosts' backlogs have, they don't know when anything was said.
+// the millis are UTC, and turning an instant into local time always has exactly one answer, so
+// `single` only gives up on timestamps chrono can't represent. DST only makes it ambiguous the other way
+fn timestamp(millis: u64) -> String {
+    if millis == 0 || !TIMESTAMPS.load(Ordering::Relaxed) {
+        return String::new();
+    }
+    let format = if TWELVE_HOUR.load(Ordering::Relaxed) { "%-I:%M:%S %P" } else { "%H:%M:%S" };
+    match Local.timestamp_millis_opt(millis as i64).single() {
+        Some(time) => format!("[{}] ", time.format(format)),
+        None => String::new(),
+    }
+}
+
+// how many peers we're directly connected to, said whenever it changes. it's gossip neighbors,
+// not everyone in the room, but zero does mean nobody will see anything
+fn show_neighbor_count(count: usize) {
+    if QUIET.load(Ordering::Relaxed) {
+        return;
+    }
+    match count {
+        0 => status("> no peers connected".blue().dim()),
+        1 => status("> 1 peer connected".blue().dim()),
+        _ => status(format!("> {count} peers connected").blue().dim()),
+    }
+}
+
+// show a message as it came off the wire. it goes to stderr so it can't get mixed up with `--json` output,
+// and it's printed as text when it's valid utf-8 (it's json after all) or as hex when it isn't
+fn dump_raw(topic: &str, from: NodeId, bytes: &[u8]) {
+    if !DUMP_RAW.load(Ordering::Relaxed) { return; }
+    let shown = match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => format!("hex {}", data_encoding::HEXLOWER.encode(bytes)),
+    };
+    eprintln!("{}", format!("[raw {topic} via {}] {shown}", from.fmt_short()).dark_grey());
+}
+
+// where `--event-log` goes, None without it
+static EVENT_LOG: Mutex<Option<PathBuf>> = Mutex::new(None);
+const EVENT_LOG_MAX_BYTES: u64 = 1024 * 1024;
+
+// one line in the event log with the local time in front. only the one older file is kept around, so
+// an overnight run can't fill the disk. a log that can't be written to just doesn't get the line,
+// nothing about chatting should fail because of it
+fn log_event(line: impl Display) {
+    let event_log = EVENT_LOG.lock().expect("should be able to acquire lock");
+    let Some(path) = event_log.as_ref() else { return };
+    if fs::metadata(path).is_ok_and(|metadata| metadata.len() >= EVENT_LOG_MAX_BYTES) {
+        let mut rotated = path.clone().into_os_string();
+        rotated.push(".1");
+        let _ = fs::rename(path, rotated);
+    }
+    if let Ok(mut file) = fs::OpenOptions::new().create(true).append(true).open(path) {
+        let _ = writeln!(file, "{} {line}", Local::now().format("%Y-%m-%d %H:%M:%S"));
+    }
+}
+
+fn status(line: impl Display) {
+    if !JSON_OUTPUT.load(Ordering::Relaxed) {
+        println!("{line}");
+    }
+}
+
+const MAX_NICKNAME_CHARS: usize = 32; // longer names wreck everyone's line wrapping
+
+// drop anything that could move the cursor, recolor someone's terminal or retitle their window. escape
+// sequences are removed as a whole so their leftover `[31m` or `]0;title` doesn't show up as text
+fn sanitize(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            match chars.next() {
+                // CSI sequences end on the first byte in the `@`..=`~` range
+                Some('[') => {
+                    for c in chars.by_ref() {
+                        if ('@'..='~').contains(&c) { break; }
+                    }
+                }
+                // OSC (titles, hyperlinks, the clipboard) and the other string sequences run until BEL or ESC \
+                Some(']' | 'P' | 'X' | '^' | '_') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) { break; }
+                    }
+                }
+                // anything else is ESC and one more character
+                _ => {}
+            }
+        } else if !c.is_control() {
+            result.push(c);
+        }
+    }
+    result
+}
+
+fn clean_nickname(name: &str) -> String {
+    sanitize(name).trim().chars().take(MAX_NICKNAME_CHARS).collect::<String>().trim_end().to_string()
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+// how far back a message's own timestamp is believed. gossip delivers in seconds, so anything older is a
+// broken clock (or an old client sending 0) and would otherwise show up as 1970
+const MAX_SENT_AT_AGE_MILLIS: u64 = 24 * 60 * 60 * 1000;
+
+// when a message was sent, by the sender's clock if it's anywhere near ours, otherwise by ours
+fn believable_sent_at(sent_at: u64, now: u64) -> u64 {
+    if sent_at > now || sent_at < now.saturating_sub(MAX_SENT_AT_AGE_MILLIS) { now } else { sent_at }
+}
+
+// one line of `--json` output. this is kept separate from `ChatMessage` on purpose,
+// so the wire format can change without breaking whatever is reading our stdout
+#[derive(Debug, Serialize)]
+struct JsonEvent {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    from: String,
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+    timestamp: u64,
+}
+
+impl JsonEvent {
+    fn emit(kind: &'static str, from: PublicKey, name: String, text: Option<String>) {
+        let event = JsonEvent { kind, from: from.to_string(), name, text, timestamp: unix_millis() };
+        println!("{}", serde_json::to_string(&event).expect("serde_json::to_string is infallible"));
+    }
+}
+
+// what goes after the header in topics and host keys. the encoding is part of it, so json and `--compact`
+// clients never end up in the same room unable to read each other. json leaves it as it always was
+fn wire_version() -> String {
+    wire_version_with(COMPACT.load(Ordering::Relaxed))
+}
+
+fn wire_version_with(compact: bool) -> String {
+    if compact { format!("{MINIMAL_VERSION}+postcard") } else { MINIMAL_VERSION.to_string() }
+}
+
+// the seed for a topic or host key. with a password it's a keyed hash, which nobody can redo without the
+// password. without one it's what it always was, so rooms without a password don't move
+fn room_seed(seed: &str) -> [u8; 32] {
+    match room_key() {
+        Some(key) => *blake3::keyed_hash(&key, seed.as_bytes()).as_bytes(),
+        None => bytes_from_str(seed),
+    }
+}
+
+// the room name is tacked on the end, so the empty room is the same one everybody used before rooms existed
+fn room_topic(room: &str) -> TopicId {
+    TopicId::from_bytes(room_seed(&(MINIMAL_TOPIC_HEADER.to_owned() + &wire_version() + room)))
+}
+
+// like the topic, so different rooms have different hosts and the empty room keeps the old one
+fn host_secret_key(room: &str) -> SecretKey {
+    host_secret_key_with(room, COMPACT.load(Ordering::Relaxed))
+}
+
+// the host key of a room that may not talk the same encoding we started with, for checking tickets
+fn host_secret_key_with(room: &str, compact: bool) -> SecretKey {
+    SecretKey::from_bytes(&room_seed(&(MINIMAL_HOST_KEY_KEADER.to_owned() + &wire_version_with(compact) + room)))
+}
+
+// the moderator of a room is whoever holds its host key, and only their kicks and backlogs are listened to.
+// the key comes from the room name, so there's no separate list of moderators to keep in sync.
+// two people running `open` for the same room end up as the same node id: they can't both be reached,
+// peers just get whichever one they connected to, and moderation doesn't try to tell them apart
+fn room_moderator(room: &str) -> PublicKey {
+    host_secret_key(room).public()
+}
+
+// the ways minimal can fail that are worth telling apart, each gets its own exit code so scripts can
+// react to them. anything else stays a plain anyhow error and exits with 1
+#[derive(Debug)]
+enum MinimalError {
+    // couldn't get online, or couldn't reach the room's host
+    Network(String),
+    // the config file couldn't be read or parsed
+    Config(String),
+    // asked for a room that doesn't exist, like an unknown bookmark
+    Room(String),
+    // a peer sent something we couldn't make sense of
+    Protocol(String),
+}
+
+impl MinimalError {
+    fn exit_code(&self) -> u8 {
+        match self {
+            MinimalError::Network(_) => 2,
+            MinimalError::Config(_) => 3,
+            MinimalError::Room(_) => 4,
+            MinimalError::Protocol(_) => 5,
+        }
+    }
+}
+
+impl Display for MinimalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MinimalError::Network(message) | MinimalError::Config(message) | MinimalError::Room(message) | MinimalError::Protocol(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for MinimalError {}
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            // an error in the middle of a game would otherwise leave the shell in raw mode
+            restore_terminal();
+            eprintln!("{}", format!("> {e:#}").red());
+            ExitCode::from(e.downcast_ref::<MinimalError>().map_or(1, MinimalError::exit_code))
+        }
+    }
+}
+
+async fn run() -> Result<()> {
     let args = Args::parse();
+    // a panic message printed in raw mode comes out as a staircase, and the shell afterwards is no better
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        restore_terminal();
+        default_hook(info);
+    }));
+    JSON_OUTPUT.store(args.json, Ordering::Relaxed);
+    BELL.store(args.bell && !args.json && !args.quiet, Ordering::Relaxed);
+    DUMP_RAW.store(args.dump_raw, Ordering::Relaxed);
+    COMPACT.store(args.compact, Ordering::Relaxed);
+    if let Some(password) = &args.password {
+        *ROOM_KEY.lock().expect("should be able to acquire lock") = Some(blake3::derive_key("the-rivulet/minimal room password", password.as_bytes()));
+    } else if !args.offline {
+        status("> this room is public, anyone who knows its name can read along or host it. use --password to lock it".yellow());
+    }
+    CONNECTION_TIMEOUT_SECS.store(args.timeout, Ordering::Relaxed);
+    MESSAGE_LIMIT.store(args.gossip_max_message_size.map_or(MAX_MESSAGE_BYTES, |size| size as usize), Ordering::Relaxed);
+    QUIET.store(args.quiet, Ordering::Relaxed);
+    if let Some(path) = &args.event_log {
+        // better to find out now than the morning after
+        if let Err(e) = fs::OpenOptions::new().create(true).append(true).open(path) {
+            status(format!("> can't write to the event log {}: {e}", path.display()).yellow());
+        }
+        *EVENT_LOG.lock().expect("should be able to acquire lock") = Some(path.clone());
+    }
+    let config_path = config_path(args.config.clone(), args.profile.as_deref());
+    if args.verbose {
+        status(format!("> using config file {}", config_path.display()).dark_grey());
+    }
+    let stats_path = config_path.with_file_name(STATS_FILE);
+    // the doctor doesn't need any of the chat setup, it does its own thing and leaves. it only looks at the
+    // config for bookmarks, so it doesn't lock it or write a new one
+    if let Command::Doctor = args.command {
+        let minconfig = MinConfig::read(&config_path)?.unwrap_or_else(|| MinConfig { path: config_path.clone(), ..MinConfig::default() });
+        return doctor(&resolve_room(&args.room, &minconfig)?).await;
+    }
+    let _config_lock = ConfigLock::acquire(&config_path);
+    let mut minconfig = MinConfig::load(config_path)?;
+    TIMESTAMPS.store(minconfig.timestamps, Ordering::Relaxed);
+    TWELVE_HOUR.store(minconfig.time_format == TimeFormat::TwelveHour, Ordering::Relaxed);
+    // a limit under the smallest playable size would make every game abort, so it can't go that low
+    MAX_PLAY_COLS.store(minconfig.max_play_cols.map_or(0, |cols| cols.max(MIN_TERM_COLS)), Ordering::Relaxed);
+    MAX_PLAY_ROWS.store(minconfig.max_play_rows.map_or(0, |rows| rows.max(MIN_TERM_ROWS)), Ordering::Relaxed);
+    let mut room = resolve_room(&args.room, &minconfig)?;
+    // a ticket knows which room it's for, and how that room talks
+    let ticket = match &args.command {
+        Command::Join { ticket: Some(ticket) } => Some(Ticket::decode(ticket)?),
+        _ => None,
+    };
+    if let Some(ticket) = &ticket {
+        if !args.room.is_empty() && ticket.room != room {
+            status(format!("> the ticket is for {}, ignoring --room", describe_room(&ticket.room)).yellow());
+        }
+        room = ticket.room.clone();
+        COMPACT.store(ticket.compact, Ordering::Relaxed);
+    }
+    // the host's address from the ticket, for as long as we're in the room it's for
+    let ticket_host = |room: &str| ticket.as_ref().and_then(|ticket| ticket.host_in(room));
     // parse the cli command
-    let topic = TopicId::from_bytes(bytes_from_str(&(MINIMAL_TOPIC_HEADER.to_owned() + MINIMAL_VERSION)));
     let (is_host_node, secret_key) = match &args.command {
+        Command::Open if args.new_identity => {
+            status(format!("> joining {} with a new identity instead of hosting it...", describe_room(&room)).blue().dim());
+            (false, SecretKey::generate(&mut rand::rng()))
+        }
         Command::Open => {
-            println!("{}", "> opening chat room as host...".blue().dim());
+            status(format!("> opening {} as host...", describe_room(&room)).blue().dim());
             // set to None because we want to become the host node
-            (true, SecretKey::from_bytes(&bytes_from_str(&(MINIMAL_HOST_KEY_KEADER.to_owned() + MINIMAL_VERSION))))
+            (true, host_secret_key(&room))
         }
-        Command::Join => {
-            println!("{}", "> attempting to join chat room...".blue().dim());
-            (false, SecretKey::generate(&mut rand::rng()))
+        Command::Join { .. } => {
+            status(format!("> attempting to join {}...", describe_room(&room)).blue().dim());
+            (false, minconfig.identity())
         }
+        Command::Doctor => unreachable!("the doctor exits before the chat is set up"),
     };
 
-    let discovery = StaticProvider::new();
-    let endpoint = Endpoint::builder()
-        .discovery_n0()
-        .add_discovery(discovery.clone())
-        .secret_key(secret_key) // if I am hosting then use the dedicated host key. if not, then use a random one
-        .bind().await?;
-
-    let gossip = Gossip::builder().spawn(endpoint.clone());
-
-    let router = Router::builder(endpoint.clone())
-        .accept(iroh_gossip::ALPN, gossip.clone())
-        .spawn();
+    let our_id = secret_key.public();
+    let gossip_tuning = GossipTuning {
+        active_view: args.gossip_active_view.map(|n| n as usize),
+        passive_view: args.gossip_passive_view.map(|n| n as usize),
+        max_message_size: args.gossip_max_message_size.map(|n| n as usize),
+    };
 
-    // read from minconfig.json if it exists
-    const CONFIG_PATH: &str = "minconfig.json";
-    let minconfig_exists = fs::exists(CONFIG_PATH)?;
-    if !minconfig_exists {
-        // assuming it does exist, we should be able to read it pretty easily
-        // otherwise it will need to be created
-        println!("{}", "> couldn't find minconfig.json, creating a new one".yellow());
-        fs::write(CONFIG_PATH, "{\n    \"name\": \"\"\n}")?;
-    }
-    let minconfig: MinConfig = serde_json::from_str(&fs::read_to_string(CONFIG_PATH)?)?;
     // quick warning if the terminal is too tiny
     let (term_cols, term_rows) = size()?;
     if (term_cols < MIN_TERM_COLS) || (term_rows < MIN_TERM_ROWS) {
-        println!("{}", format!("> terminal is too small to play, should be at least {MIN_TERM_COLS} x {MIN_TERM_ROWS}.").yellow());
+        status(format!("> terminal is too small to play, should be at least {MIN_TERM_COLS} x {MIN_TERM_ROWS}.").yellow());
     }
 
-    println!("{}", "> connecting to the network...".blue().dim());
-    let wait_for_online = endpoint.online();
-    if let Err(_) = tokio::time::timeout(Duration::from_secs(CONNECTION_TIMEOUT_SECS), wait_for_online).await {
-        panic!("{}", std::io::Error::new(
-            ErrorKind::NetworkUnreachable,
-            format!("couldn't get online within {} seconds", CONNECTION_TIMEOUT_SECS)
-        ));
-    }
-    // join the gossip topic by connecting to known nodes, if any
-    let bootstrap_nodes = if is_host_node {
-        println!("{}", "> server started, waiting for nodes to join us".blue());
-        vec![]
+    let (network, sender, receiver) = if args.offline {
+        status("> offline mode, nothing you type will leave this computer".yellow());
+        (None, Outbox::Offline, None)
     } else {
-        println!("{}", "> trying to reach host node...".blue().dim());
-        // mimic the logic used to generate the host key
-        let host_key = &bytes_from_str(&(MINIMAL_HOST_KEY_KEADER.to_owned() + MINIMAL_VERSION));
-        let host_addr = NodeAddr::new(SecretKey::from_bytes(host_key).public())
-            .with_relay_url(endpoint.node_addr().relay_url.ok_or(
-                std::io::Error::new(ErrorKind::Other, "node should have a relay_url")
-            )?);
-        discovery.add_node_info(host_addr.clone());
-        // I feel a bit concerned with the amount of `.clone()` here
-        vec![host_addr.node_id]
-    };
-    let sender; let receiver;
-    let output = if is_host_node {
-        Ok(gossip.subscribe_and_join(topic, bootstrap_nodes).await)
-    } else {
-        tokio::time::timeout(
-            Duration::from_secs(CONNECTION_TIMEOUT_SECS),
-            gossip.subscribe_and_join(topic, bootstrap_nodes)
-        ).await
+        // the host uses the room's host key, everyone else the identity kept in the config, or a fresh one
+        // with --new-identity
+        let network = start_network(secret_key, &gossip_tuning, local_bind(args.local, is_host_node, &room)).await?;
+        // join the gossip topic by connecting to known nodes, if any
+        let (sender, receiver) = if is_host_node {
+            let topic = host_room(&room, &network.gossip, args.host_attempts).await?;
+            status("> server started, waiting for nodes to join us".blue());
+            topic
+        } else {
+            status("> trying to reach host node...".blue().dim());
+            join_room(&room, &network, ticket_host(&room)).await?
+        };
+        let sender = Outbox::gossip(sender, &network.endpoint);
+        (Some(network), sender, Some(receiver))
     };
-    match output {
-        Ok(value) => { (sender, receiver) = value?.split(); }
-        Err(_) => panic!("{}", std::io::Error::new(
-            ErrorKind::NetworkUnreachable,
-            format!("couldn't connect to host within {} seconds, maybe try `cargo run open` to start a server?", CONNECTION_TIMEOUT_SECS)
-        ))
+    if args.verbose && let Some(network) = &network {
+        show_relays(network);
+    }
+    status("> ready!".blue().bold());
+    log_event(format!("started as {} in {}", our_id.fmt_short(), describe_room(&room)));
+    if let Some(ticket) = network.as_ref().and_then(|network| Ticket::for_host(&room, &network.endpoint)) {
+        status(format!("> ticket: {}", join_command(&room, Some(&ticket))).blue());
+        if args.copy {
+            copy_join_command(&room, Some(&ticket));
+        }
     }
-    println!("{}", "> ready!".blue().bold());
 
     // broadcast our name, if set
-    let my_nickname = if let Some(argument_name) = args.name {
-        Some(argument_name)
+    let my_nickname = if let Some(argument_name) = &args.name {
+        Some(argument_name.clone())
     } else if !minconfig.name.is_empty() {
-        Some(minconfig.name)
+        Some(minconfig.name.clone())
     } else {
         None
     };
-    if let Some(name) = my_nickname {
-        let message = MinimalMessage::new(MinimalMessageType::Chat(ChatMessage::AboutMe {
-            from: endpoint.node_id(),
-            name,
-        }));
-        sender.broadcast(message.to_vec().into()).await?;
-    }
+    // the first thing everyone sees from us should be well-formed, so clean the name the same way /nick does
+    let my_nickname = my_nickname.and_then(|name| {
+        let cleaned = clean_nickname(&name);
+        if cleaned.is_empty() {
+            status(format!("> ignoring nickname `{}`, it has no usable characters", name.escape_debug()).yellow());
+            None
+        } else {
+            if cleaned != name {
+                status(format!("> nickname was too long or had control characters, using `{cleaned}` instead").yellow());
+            }
+            Some(cleaned)
+        }
+    });
+    // only used to label our own lines in `--json` mode
+    let our_name = my_nickname.clone().unwrap_or_else(|| our_id.fmt_short().to_string());
+    // count neighbors as the subscribe loop sees them come and go. joining already waited for one,
+    // so start from whatever the receiver knows about rather than zero
+    let (neighbor_tx, neighbor_rx) = tokio::sync::watch::channel(receiver.as_ref().map_or(0, |receiver| receiver.neighbors().count()));
+    let name_announcer = tokio::spawn(announce_presence(sender.clone(), neighbor_rx.clone(), our_id, my_nickname.clone()));
 
-    // variable to keep track of game requests
-    let game_request_tracker = Arc::new(Mutex::new(None));
-    let our_id = endpoint.node_id();
-    // create an arc to store the gossip because we may need to use it when starting a game
-    let gossip_arc = Arc::new(gossip);
-    // subscribe and print loop
-    tokio::spawn(subscribe_loop(receiver, our_id, gossip_arc.clone(), game_request_tracker.clone()));
-    // something questionable is going on with that `.clone()`
+    // what we'd like our games to look like, sent along with requests so the other side can agree to it
+    let our_game_config = GameConfig {
+        starting_bits: args.game_bits.unwrap_or(GameConfig::default().starting_bits),
+        colors: args.game_colors.map_or(GameConfig::default().colors, |colors| colors as usize),
+        skills: args.game_skills.map_or(GameConfig::default().skills, |skills| skills as usize),
+    };
+    // an empty prefix would make every line a command
+    let command_prefix = if minconfig.command_prefix.is_empty() {
+        status(format!("> command_prefix in {} is empty, using / instead", minconfig.path.display()).yellow());
+        default_command_prefix()
+    } else {
+        minconfig.command_prefix.clone()
+    };
+    // create a multi-provider, single-consumer channel for lines to handle
+    let (line_tx, mut line_rx) = tokio::sync::mpsc::channel(1);
+    let shared = SharedState {
+        our_id,
+        moderator: room_moderator(&room),
+        our_name: Arc::new(Mutex::new(our_name.clone())),
+        stats_path,
+        gossip: network.as_ref().map(|network| network.gossip.clone()),
+        endpoint: network.as_ref().map(|network| network.endpoint.clone()),
+        discovery: network.as_ref().map(|network| network.discovery.clone()),
+        chat_sender: sender.clone(),
+        commands: line_tx.clone(),
+        command_prefix,
+        names: Arc::new(Mutex::new(HashMap::new())),
+        game_request_tracker: Arc::new(Mutex::new(None)),
+        last_message: Arc::new(Mutex::new(None)),
+        active_games: Arc::new(Mutex::new(BTreeMap::new())),
+        history_limit: minconfig.history_limit,
+        backlog: Arc::new(Mutex::new(VecDeque::new())),
+        scrollback: Arc::new(Mutex::new(VecDeque::new())),
+        transcript: args.log.as_ref().and_then(|path| match fs::OpenOptions::new().create(true).append(true).open(path) {
+            Ok(file) => Some(Arc::new(Mutex::new(file))),
+            Err(e) => {
+                status(format!("> can't write the chat log to {} ({e}), carrying on without it", path.display()).yellow());
+                None
+            }
+        }),
+        backlog_size: args.backlog,
+        topic_line: Arc::new(Mutex::new(None)),
+        allowlist: Arc::new(args.allow.iter().copied().collect()),
+        last_active: Arc::new(Mutex::new(Instant::now())),
+        neighbors: Arc::new(Mutex::new(receiver.as_ref().map_or_else(HashSet::new, |receiver| receiver.neighbors().collect()))),
+    };
+    if !args.allow.is_empty() && !is_host_node {
+        status("> --allow only does anything for the room's host, ignoring it".yellow());
+    }
+    // subscribe and print loop, kept so it can be swapped out by /join.
+    // offline there's nothing to subscribe to, so nothing ever comes in
+    let subscription = receiver.map(|receiver| tokio::spawn(watch_subscription(receiver, shared.clone(), neighbor_tx)));
 
     // spawn an input thread that reads stdin
-    // create a multi-provider, single-consumer channel
-    let (line_tx, mut line_rx) = tokio::sync::mpsc::channel(1);
     // and pass the `sender` portion to the `input_loop`
     std::thread::spawn(move || input_loop(line_tx));
 
-    // broadcast each line we type
+    if args.quickplay {
+        tokio::spawn(quickplay(shared.commands.clone(), neighbor_rx.clone(), network.is_some(), shared.command_prefix.clone()));
+    }
+
+    // for kiosk-ish setups, leave by ourselves once nobody has typed anything in a while
+    let idle_timeout = (args.idle_timeout > 0).then(|| Duration::from_secs(args.idle_timeout));
+    let mut last_input = Instant::now();
+    let mut warned_idle = false;
+
+    let mut session = Session {
+        args,
+        minconfig,
+        room,
+        ticket,
+        network,
+        sender,
+        shared,
+        our_name,
+        my_nickname,
+        gossip_tuning,
+        our_game_config,
+        neighbor_rx,
+        subscription,
+        name_announcer: Some(name_announcer),
+        last_sent: None,
+        quitting: false,
+    };
+
     // listen for lines that we have typed to be sent from `stdin`
-    while let Some(text) = line_rx.recv().await {
-        // create a message from the text
-        if text.starts_with("/") {
-            let arguments: Vec<_> = text.trim().split(" ").collect();
-            if arguments[0] == "/nick" {
-                let new_nick = arguments[1..].join(" ");
-                let message = MinimalMessage::new(MinimalMessageType::Chat(ChatMessage::AboutMe {
-                    from: endpoint.node_id(),
-                    name: new_nick.to_string(),
-                }));
-                // broadcast the encoded message
-                sender.broadcast(message.to_vec().into()).await?;
-                // print a confirmation message
-                println!("{}", format!("> you changed your nickname to {new_nick}").green());
-            } else if arguments[0] == "/quit" {
-                break;
-            } else if arguments[0] == "/min" {
-                // lock will be released at end of scope
-                let mut requester = game_request_tracker.lock().expect("should be able to acquire lock");
-                match *requester {
-                    Some(other_requester) => {
-                        let game_id = rand::random_range(0.0..=1e9);
-                        let message = MinimalMessage::new(MinimalMessageType::Chat(ChatMessage::GameStart {
-                            from: endpoint.node_id(),
-                            orig_sender: other_requester,
-                            game_id: game_id
-                        }));
-                        sender.broadcast(message.to_vec().into()).await?;
-                        *requester = None; // the queue has been emptied
-                        println!("{}", "> ok, starting a game!".green());
-                        tokio::spawn(begin_game(game_id, gossip_arc.clone(), vec![]));
+    while !session.quitting {
+        let text = match idle_timeout {
+            None => line_rx.recv().await,
+            Some(idle_timeout) => {
+                // wake up once for the warning and once more to actually leave
+                let wake_at = if warned_idle { idle_timeout } else { idle_timeout.saturating_sub(IDLE_WARNING) };
+                match tokio::time::timeout(wake_at.saturating_sub(last_input.elapsed()), line_rx.recv()).await {
+                    Ok(text) => text,
+                    Err(_) if warned_idle => {
+                        status("> no input for too long, leaving".yellow());
+                        break;
                     }
-                    None => {
-                        let message = MinimalMessage::new(MinimalMessageType::Chat(ChatMessage::GameRequest {
-                            from: endpoint.node_id(),
-                        }));
-                        sender.broadcast(message.to_vec().into()).await?;
-                        *requester = Some(endpoint.node_id()); // we are requesting
-                        println!("{}", format!("> joined the minimal queue!").green());
+                    Err(_) => {
+                        warned_idle = true;
+                        let left = idle_timeout.saturating_sub(last_input.elapsed()).as_secs();
+                        status(format!("> no input for a while, leaving in {left} seconds unless you type something").yellow());
+                        continue;
                     }
-                } // released here
-            } else {
-                println!("{}", format!("unknown command: {}", text.trim()).red());
+                }
             }
-        } else {
-            let message = MinimalMessage::new(MinimalMessageType::Chat(ChatMessage::Message {
-                from: endpoint.node_id(),
-                text: text.clone(),
-            }));
-            // broadcast the encoded message
-            sender.broadcast(message.to_vec().into()).await?;
+        };
+        let Some(text) = text else { break };
+        last_input = Instant::now();
+        warned_idle = false;
+        *session.shared.last_active.lock().expect("should be able to acquire lock") = last_input;
+        match parse_input(&text, &session.shared.command_prefix) {
+            Input::Command(text) => session.run_command(&text).await?,
+            Input::Chat(text) => session.say(text).await?,
         }
     }
-    router.shutdown().await?;
+    log_event("left");
+    // let the room know we're gone, whether that was /quit or the idle timeout
+    let message = MinimalMessage::new(MinimalMessageType::Chat(ChatMessage::Left { from: session.shared.our_id }));
+    session.sender.broadcast(&message).await?;
+    if let Some(network) = session.network {
+        network.router.shutdown().await?;
+    }
 
     Ok(())
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct MinimalMessage {
-    body: MinimalMessageType,
-    nonce: [u8; 16],
+// everything the slash commands work with. `run` sets it up and hands it each line that's typed, and
+// commands are free to swap any of it out, like /join does with the room or /rekey with the network
+struct Session {
+    args: Args,
+    minconfig: MinConfig,
+    room: String,
+    // what `join` was given, if anything
+    ticket: Option<Ticket>,
+    // missing in `--offline` mode
+    network: Option<Network>,
+    sender: Outbox,
+    shared: SharedState,
+    // what our own lines are labeled with, the nickname if there is one and the short id if not
+    our_name: String,
+    // the nickname we picked, which is what gets announced to each room
+    my_nickname: Option<String>,
+    gossip_tuning: GossipTuning,
+    // what we'd like our games to look like, sent along with requests so the other side can agree to it
+    our_game_config: GameConfig,
+    neighbor_rx: tokio::sync::watch::Receiver<usize>,
+    // the subscribe loop and the name announcement for the topic we're on, both replaced on every join
+    subscription: Option<tokio::task::JoinHandle<()>>,
+    name_announcer: Option<tokio::task::JoinHandle<Result<()>>>,
+    // our newest message, which is what /edit and /delete act on
+    last_sent: Option<[u8; 16]>,
+    // set by /quit, the input loop stops after the command that set it
+    quitting: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-enum MinimalMessageType {
-    Chat(ChatMessage),
-    Game(GameMessage),
-}
+impl Session {
+    // a line that started with the command prefix, already spelled with `/`
+    async fn run_command(&mut self, text: &str) -> Result<()> {
+        let arguments: Vec<_> = text.trim().split(" ").collect();
+        let Some(command) = find_command(arguments[0]) else {
+            status(format!("unknown command: {}, these are the ones there are:", text.trim()).red());
+            show_help(&self.shared.command_prefix);
+            return Ok(());
+        };
+        let call = Call { command, args: &arguments[1..] };
+        if let Params::Required(_) = command.params && call.rest().trim().is_empty() {
+            self.show_usage(command);
+            return Ok(());
+        }
+        (command.run)(self, call).await
+    }
+
+    fn show_usage(&self, command: &SlashCommand) {
+        status(format!("usage: {}", command.usage(&self.shared.command_prefix)).red());
+    }
+
+    fn ticket_host(&self, room: &str) -> Option<NodeAddr> {
+        self.ticket.as_ref().and_then(|ticket| ticket.host_in(room))
+    }
+
+    // anything that isn't a command goes to the room as it is
+    async fn say(&mut self, text: String) -> Result<()> {
+        let our_id = self.shared.our_id;
+        let message = MinimalMessage::new(MinimalMessageType::Chat(ChatMessage::Message {
+            from: our_id,
+            text: text.clone(),
+            sent_at: unix_millis(),
+        }));
+        if !self.sender.fits(&message) {
+            status(format!("> that's too long to send, messages can be at most {} bytes", message_limit()).red());
+            return Ok(());
+        }
+        // broadcast the encoded message
+        self.sender.broadcast(&message).await?;
+        self.last_sent = Some(message.nonce);
+        transcribe(&self.shared, &format!("{}: {}", self.our_name, text.trim()));
+        remember_for_backlog(&self.shared, BacklogEntry { from: our_id, name: self.our_name.clone(), text: text.trim().to_string(), at: unix_millis() });
+        // gossip doesn't keep anything for later, so with no neighbors the message is simply gone
+        if self.network.is_some() && *self.neighbor_rx.borrow() == 0 {
+            status("> nobody else is in the room yet, so nobody saw that".yellow());
+        }
+        if JSON_OUTPUT.load(Ordering::Relaxed) {
+            JsonEvent::emit("sent", our_id, self.our_name.clone(), Some(text.trim().to_string()));
+        }
+        Ok(())
+    }
+
+    // stop hearing from the topic we're on. dropping the old sender and receiver, which `listen` does,
+    // is what actually leaves it
+    fn stop_listening(&mut self) {
+        if let Some(subscription) = self.subscription.take() { subscription.abort(); }
+        if let Some(announcer) = self.name_announcer.take() { announcer.abort(); }
+    }
+
+    // start hearing from a topic we just joined, and tell it who we are. others only know our name by
+    // node id, so even the same room needs to hear it again after a new identity
+    fn listen(&mut self, sender: Outbox, receiver: GossipReceiver) {
+        self.sender = sender;
+        self.shared.chat_sender = self.sender.clone();
+        let (neighbor_tx, neighbor_rx) = tokio::sync::watch::channel(receiver.neighbors().count());
+        self.neighbor_rx = neighbor_rx;
+        self.subscription = Some(tokio::spawn(watch_subscription(receiver, self.shared.clone(), neighbor_tx)));
+        self.name_announcer = Some(tokio::spawn(announce_presence(self.sender.clone(), self.neighbor_rx.clone(), self.shared.our_id, self.my_nickname.clone())));
+    }
+
+    async fn help(&mut self, _call: Call<'_>) -> Result<()> {
+        show_help(&self.shared.command_prefix);
+        Ok(())
+    }
+
+    async fn settings(&mut self, _call: Call<'_>) -> Result<()> {
+        // what it is right now, then where that came from. only /timestamps changes anything at runtime
+        let prefix = &self.shared.command_prefix;
+        let on_off = |on: bool| if on { "on" } else { "off" };
+        let setting = |name: &str, now: &str, from: String| status(format!("> {name:<14} {now:<8} {}", from.dark_grey()).blue());
+        setting("timestamps", on_off(TIMESTAMPS.load(Ordering::Relaxed)), format!("config says {}, change with {prefix}timestamps", on_off(self.minconfig.timestamps)));
+        let time_format = if TWELVE_HOUR.load(Ordering::Relaxed) { "12h" } else { "24h" };
+        setting("time format", time_format, "time_format in the config".to_string());
+        setting("prefix", prefix, "command_prefix in the config".to_string());
+        setting("history", &self.shared.history_limit.to_string(), "history_limit in the config".to_string());
+        let play_area = match (self.minconfig.max_play_cols, self.minconfig.max_play_rows) {
+            (None, None) => "fill".to_string(),
+            (cols, rows) => format!("{}x{}", cols.map_or("any".to_string(), |cols| cols.to_string()), rows.map_or("any".to_string(), |rows| rows.to_string())),
+        };
+        setting("play area", &play_area, "max_play_cols and max_play_rows in the config".to_string());
+        setting("bell", on_off(BELL.load(Ordering::Relaxed)), "--bell".to_string());
+        setting("quiet", on_off(QUIET.load(Ordering::Relaxed)), "--quiet".to_string());
+        setting("password", on_off(room_key().is_some()), "--password or MINIMAL_PASSWORD".to_string());
+        setting("event log", self.args.event_log.as_ref().map_or("off".to_string(), |path| path.display().to_string()).as_str(), "--event-log".to_string());
+        status(format!("> config file is {}", self.minconfig.path.display()).dark_grey());
+        Ok(())
+    }
+
+    async fn me(&mut self, call: Call<'_>) -> Result<()> {
+        let text = call.rest().trim().to_string();
+        let message = MinimalMessage::new(MinimalMessageType::Chat(ChatMessage::Action { from: self.shared.our_id, text: text.clone() }));
+        if !self.sender.fits(&message) {
+            status(format!("> that's too long to send, messages can be at most {} bytes", message_limit()).red());
+            return Ok(());
+        }
+        self.sender.broadcast(&message).await?;
+        if JSON_OUTPUT.load(Ordering::Relaxed) {
+            JsonEvent::emit("sent_action", self.shared.our_id, self.our_name.clone(), Some(text));
+        }
+        Ok(())
+    }
+
+    async fn nick(&mut self, call: Call<'_>) -> Result<()> {
+        let requested = call.rest();
+        let new_nick = clean_nickname(&requested);
+        if new_nick.is_empty() {
+            // nothing to broadcast, and an empty name would make our lines look like nobody said them
+            status(format!("> `{}` has no usable characters, nickname not changed", requested.escape_debug()).red());
+            return Ok(());
+        }
+        if new_nick != requested.trim() {
+            status(format!("> nickname was too long or had control characters, using `{new_nick}` instead").yellow());
+        }
+        // a late retry of the startup name would undo this one
+        if let Some(announcer) = self.name_announcer.take() { announcer.abort(); }
+        if self.network.is_some() && *self.neighbor_rx.borrow() == 0 {
+            // nobody would hear it right now, so hold on to it until someone shows up
+            self.name_announcer = Some(tokio::spawn(announce_presence(self.sender.clone(), self.neighbor_rx.clone(), self.shared.our_id, Some(new_nick.clone()))));
+            status("> nobody else is in the room yet, they'll get your new name when they join".yellow());
+        } else {
+            let message = MinimalMessage::new(MinimalMessageType::Chat(ChatMessage::AboutMe {
+                from: self.shared.our_id,
+                name: new_nick.to_string(),
+            }));
+            // broadcast the encoded message
+            self.sender.broadcast(&message).await?;
+        }
+        // print a confirmation message
+        status(format!("> you changed your nickname to {new_nick}").green());
+        self.my_nickname = Some(new_nick.clone());
+        self.our_name = new_nick;
+        *self.shared.our_name.lock().expect("should be able to acquire lock") = self.our_name.clone();
+        Ok(())
+    }
+
+    async fn history(&mut self, call: Call<'_>) -> Result<()> {
+        let scrollback = self.shared.scrollback.lock().expect("should be able to acquire lock");
+        let count = match call.args.first().map(|count| count.parse::<usize>()) {
+            None => scrollback.len(),
+            Some(Ok(count)) => count,
+            Some(Err(_)) => {
+                self.show_usage(call.command);
+                return Ok(());
+            }
+        };
+        if scrollback.is_empty() {
+            status("> nothing has been said yet".blue().dim());
+        }
+        for entry in scrollback.iter().skip(scrollback.len().saturating_sub(count)) {
+            status(format!("  {}{}: {}", timestamp(entry.at), entry.name, entry.text).dark_grey());
+        }
+        Ok(())
+    }
+
+    async fn timestamps(&mut self, call: Call<'_>) -> Result<()> {
+        let on = match call.args.first() {
+            Some(&"on") => true,
+            Some(&"off") => false,
+            _ => {
+                self.show_usage(call.command);
+                return Ok(());
+            }
+        };
+        TIMESTAMPS.store(on, Ordering::Relaxed);
+        self.minconfig.timestamps = on;
+        self.minconfig.save()?;
+        status(format!("> timestamps are now {}", if on { "on" } else { "off" }).green());
+        Ok(())
+    }
+
+    async fn bookmark(&mut self, call: Call<'_>) -> Result<()> {
+        let label = call.rest();
+        if self.minconfig.bookmarks.contains_key(&label) {
+            status(format!("> there is already a bookmark called `{label}`, pick another name").red());
+        } else {
+            self.minconfig.bookmarks.insert(label.clone(), Bookmark { room: self.room.clone() });
+            self.minconfig.save()?;
+            status(format!("> saved {} as `{label}`, come back with --room @{label}", describe_room(&self.room)).green());
+        }
+        Ok(())
+    }
+
+    async fn react(&mut self, call: Call<'_>) -> Result<()> {
+        let emoji = call.rest();
+        let target = *self.shared.last_message.lock().expect("should be able to acquire lock");
+        if !REACTIONS.contains(&emoji.as_str()) {
+            status(format!("usage: {}, where emoji is one of {}", call.command.usage(&self.shared.command_prefix), REACTIONS.join(" ")).red());
+        } else if let Some(target_nonce) = target {
+            let message = MinimalMessage::new(MinimalMessageType::Chat(ChatMessage::Reaction {
+                from: self.shared.our_id,
+                target_nonce,
+                emoji: emoji.clone(),
+            }));
+            self.sender.broadcast(&message).await?;
+            status(format!("> you reacted {emoji} to the last message").green());
+        } else {
+            status("> there is no message to react to yet".red());
+        }
+        Ok(())
+    }
+
+    // /edit and /delete both act on our newest message. false if there wasn't one, or the change
+    // didn't fit
+    async fn change_last_sent(&mut self, body: impl FnOnce(NodeId, [u8; 16]) -> ChatMessage) -> Result<bool> {
+        let Some(target_nonce) = self.last_sent else {
+            status("> you haven't sent anything here yet".red());
+            return Ok(false);
+        };
+        let message = MinimalMessage::new(MinimalMessageType::Chat(body(self.shared.our_id, target_nonce)));
+        if !self.sender.fits(&message) {
+            status(format!("> that's too long to send, messages can be at most {} bytes", message_limit()).red());
+            return Ok(false);
+        }
+        self.sender.broadcast(&message).await?;
+        Ok(true)
+    }
+
+    async fn edit(&mut self, call: Call<'_>) -> Result<()> {
+        let new_text = call.rest();
+        if self.change_last_sent(|from, target_nonce| ChatMessage::Edit { from, target_nonce, new_text: new_text.clone() }).await? {
+            status(format!("> edited your last message to: {new_text}").green());
+        }
+        Ok(())
+    }
+
+    async fn delete(&mut self, _call: Call<'_>) -> Result<()> {
+        if self.change_last_sent(|from, target_nonce| ChatMessage::Delete { from, target_nonce }).await? {
+            self.last_sent = None;
+            status("> deleted your last message".green());
+        }
+        Ok(())
+    }
+
+    async fn join(&mut self, call: Call<'_>) -> Result<()> {
+        let Some(network) = &self.network else {
+            status("> there are no other rooms in offline mode".red());
+            return Ok(());
+        };
+        let new_room = match resolve_room(&call.rest(), &self.minconfig) {
+            Ok(new_room) => new_room,
+            Err(e) => { status(format!("> {e}").red()); return Ok(()); }
+        };
+        if new_room == self.room {
+            status(format!("> you are already in {}", describe_room(&self.room)).yellow());
+            return Ok(());
+        }
+        status(format!("> joining {}...", describe_room(&new_room)).blue().dim());
+        match join_room(&new_room, network, self.ticket_host(&new_room)).await {
+            Ok((new_sender, new_receiver)) => {
+                let new_sender = Outbox::gossip(new_sender, &network.endpoint);
+                // say goodbye while the old room can still hear us
+                let message = MinimalMessage::new(MinimalMessageType::Chat(ChatMessage::Left { from: self.shared.our_id }));
+                self.sender.broadcast(&message).await?;
+                self.stop_listening();
+                // none of this means anything in the new room
+                *self.shared.game_request_tracker.lock().expect("should be able to acquire lock") = None;
+                self.last_sent = None;
+                *self.shared.last_message.lock().expect("should be able to acquire lock") = None;
+                self.shared.active_games.lock().expect("should be able to acquire lock").clear();
+                self.shared.moderator = room_moderator(&new_room);
+                self.shared.backlog.lock().expect("should be able to acquire lock").clear();
+                *self.shared.topic_line.lock().expect("should be able to acquire lock") = None;
+                // same name, same identity, the new room just needs to hear about it
+                self.listen(new_sender, new_receiver);
+                self.room = new_room;
+                status(format!("> you are now in {}", describe_room(&self.room)).green());
+                log_event(format!("moved to {}", describe_room(&self.room)));
+            }
+            Err(e) => status(format!("> couldn't join {} ({e}), staying in {}", describe_room(&new_room), describe_room(&self.room)).red()),
+        }
+        Ok(())
+    }
+
+    async fn rejoin(&mut self, _call: Call<'_>) -> Result<()> {
+        let Some(network) = &self.network else {
+            status("> there is no room to rejoin in offline mode".red());
+            return Ok(());
+        };
+        let joined = if self.shared.our_id == room_moderator(&self.room) {
+            host_room(&self.room, &network.gossip, self.args.host_attempts).await
+        } else {
+            join_room(&self.room, network, self.ticket_host(&self.room)).await
+        };
+        match joined {
+            Ok((new_sender, new_receiver)) => {
+                let new_sender = Outbox::gossip(new_sender, &network.endpoint);
+                self.stop_listening();
+                self.listen(new_sender, new_receiver);
+                status(format!("> back in {}", describe_room(&self.room)).green());
+                log_event(format!("rejoined {}", describe_room(&self.room)));
+            }
+            Err(e) => {
+                status(format!("> couldn't rejoin {} ({e}), try {}rejoin again in a bit", describe_room(&self.room), self.shared.command_prefix).red());
+                log_event(format!("couldn't rejoin {}: {e}", describe_room(&self.room)));
+            }
+        }
+        Ok(())
+    }
+
+    async fn rekey(&mut self, _call: Call<'_>) -> Result<()> {
+        if self.network.is_none() {
+            status("> there is no identity to rotate in offline mode".red());
+            return Ok(());
+        }
+        // everyone finds the room through the host's key, so the host has to keep it
+        if self.shared.our_id == room_moderator(&self.room) {
+            status("> you are hosting this room, changing your key would leave everyone unable to find it".red());
+            return Ok(());
+        }
+        if self.shared.active_games.lock().expect("should be able to acquire lock").values().any(|game| game.player_ids.contains(&self.shared.our_id)) {
+            status("> your game is tied to your current identity and will end when it changes".yellow());
+        }
+        status("> switching to a new identity...".blue().dim());
+        let new_key = SecretKey::generate(&mut rand::rng());
+        let new_network = match start_network(new_key.clone(), &self.gossip_tuning, local_bind(self.args.local, false, &self.room)).await {
+            Ok(new_network) => new_network,
+            Err(e) => { status(format!("> couldn't start a new identity ({e}), keeping the old one").red()); return Ok(()); }
+        };
+        match join_room(&self.room, &new_network, self.ticket_host(&self.room)).await {
+            Ok((new_sender, new_receiver)) => {
+                self.stop_listening();
+                if let Some(old_network) = self.network.take() {
+                    old_network.router.shutdown().await?;
+                }
+                // the old node id just goes quiet for everyone else, there's no way to tie it to the new one
+                let our_id = new_network.endpoint.node_id();
+                if self.my_nickname.is_none() {
+                    self.our_name = our_id.fmt_short().to_string();
+                    *self.shared.our_name.lock().expect("should be able to acquire lock") = self.our_name.clone();
+                }
+                self.shared.our_id = our_id;
+                self.shared.gossip = Some(new_network.gossip.clone());
+                self.shared.endpoint = Some(new_network.endpoint.clone());
+                self.shared.discovery = Some(new_network.discovery.clone());
+                // a pending request was made by the old id, nobody would start a game with this one
+                *self.shared.game_request_tracker.lock().expect("should be able to acquire lock") = None;
+                self.last_sent = None;
+                let new_sender = Outbox::gossip(new_sender, &new_network.endpoint);
+                self.network = Some(new_network);
+                self.listen(new_sender, new_receiver);
+                status(format!("> you are now {}", our_id.fmt_short()).green());
+                // the old key is gone for good, next time starts out as this one
+                self.minconfig.remember_identity(&new_key);
+            }
+            Err(e) => {
+                new_network.router.shutdown().await?;
+                status(format!("> couldn't rejoin {} with a new identity ({e}), keeping the old one", describe_room(&self.room)).red());
+            }
+        }
+        Ok(())
+    }
+
+    async fn stats(&mut self, _call: Call<'_>) -> Result<()> {
+        match Stats::load(&self.shared.stats_path) {
+            Ok(mut stats) => {
+                let record = stats.nodes.remove(&self.shared.our_id).unwrap_or_default();
+                status(format!("> {}: {} won, {} lost, {} aborted", self.our_name, record.wins, record.losses, record.aborts).blue());
+            }
+            Err(e) => status(format!("> couldn't read {}: {e}", self.shared.stats_path.display()).red()),
+        }
+        Ok(())
+    }
+
+    async fn copy(&mut self, _call: Call<'_>) -> Result<()> {
+        let ticket = self.shared.endpoint.as_ref().and_then(|endpoint| Ticket::for_host(&self.room, endpoint));
+        copy_join_command(&self.room, ticket.as_ref());
+        Ok(())
+    }
+
+    async fn version(&mut self, _call: Call<'_>) -> Result<()> {
+        // only clients with the same version share topics, so this is the first thing to compare
+        // when someone can't see anyone
+        status(format!("> minimal {MINIMAL_VERSION}, protocol {MINIMAL_TOPIC_HEADER}{}", wire_version()).blue());
+        status(format!("> you are {} in {}", self.shared.our_id, describe_room(&self.room)).blue());
+        Ok(())
+    }
+
+    async fn show_room(&mut self, _call: Call<'_>) -> Result<()> {
+        // the topic is all gossip knows about, two people only meet if theirs match exactly
+        let role = if self.shared.our_id == room_moderator(&self.room) { "hosting" } else { "joined" };
+        status(format!("> {role} {}, protocol {MINIMAL_TOPIC_HEADER}{}", describe_room(&self.room), wire_version()).blue());
+        status(format!("> topic {}", data_encoding::HEXLOWER.encode(room_topic(&self.room).as_bytes())).blue());
+        if let Some(text) = self.shared.topic_line.lock().expect("should be able to acquire lock").as_ref() {
+            status(format!("> room topic: {text}").blue());
+        }
+        Ok(())
+    }
+
+    async fn relay(&mut self, _call: Call<'_>) -> Result<()> {
+        match &self.network {
+            Some(network) => show_relays(network),
+            None => status("> there are no relays in offline mode".red()),
+        }
+        Ok(())
+    }
+
+    async fn share(&mut self, call: Call<'_>) -> Result<()> {
+        let path = call.rest();
+        let hash = match hash_file(PathBuf::from(&path)).await {
+            Ok(hash) => hash,
+            Err(err) => {
+                status(format!("> couldn't read {path}: {err}").red());
+                return Ok(());
+            }
+        };
+        let name = clean_attachment_name(&path);
+        let message = MinimalMessage::new(MinimalMessageType::Chat(ChatMessage::Attachment { from: self.shared.our_id, name: name.clone(), hash }));
+        self.sender.broadcast(&message).await?;
+        status(format!("> shared {name} ({})", short_hash(&hash)).green());
+        status("> only the reference went out, nobody can fetch the file from you yet".dark_grey());
+        Ok(())
+    }
+
+    async fn set_topic(&mut self, call: Call<'_>) -> Result<()> {
+        let text = clean_topic_line(&call.rest());
+        if self.network.is_none() || self.shared.our_id != room_moderator(&self.room) {
+            status("> only the room's moderator (whoever opened it) can set the topic".red());
+            return Ok(());
+        }
+        // what's left once control characters are gone can still be nothing
+        if text.is_empty() {
+            self.show_usage(call.command);
+            return Ok(());
+        }
+        *self.shared.topic_line.lock().expect("should be able to acquire lock") = Some(text.clone());
+        let message = MinimalMessage::new(MinimalMessageType::Chat(ChatMessage::RoomTopic { from: self.shared.our_id, text: text.clone() }));
+        self.sender.broadcast(&message).await?;
+        status(format!("> set the room topic to: {text}").green());
+        Ok(())
+    }
+
+    async fn list(&mut self, _call: Call<'_>) -> Result<()> {
+        let our_id = self.shared.our_id;
+        let names = self.shared.names.lock().expect("should be able to acquire lock");
+        let neighbors = self.shared.neighbors.lock().expect("should be able to acquire lock");
+        let marker = |id: PublicKey| if id == self.shared.moderator { " (moderator)" } else { "" };
+        status(format!("> {} (you, {}){}", self.our_name, our_id.fmt_short(), marker(our_id)).blue());
+        // names are everyone we've heard from, neighbors only who we're connected to directly.
+        // someone connected who hasn't said a name yet goes by their short id, like in chat
+        let mut everyone: Vec<_> = names.keys().chain(neighbors.iter()).copied().collect::<HashSet<_>>().into_iter().collect();
+        everyone.sort_by_key(|id| get_name(&names, *id).to_lowercase());
+        for id in &everyone {
+            status(format!("> {} ({}){}", get_name(&names, *id), id.fmt_short(), marker(*id)).blue());
+        }
+        if everyone.is_empty() {
+            status("> nobody else has shown up yet".blue().dim());
+        }
+        if self.network.is_some() {
+            status(format!("> connected directly to {} peers", *self.neighbor_rx.borrow()).blue().dim());
+        }
+        Ok(())
+    }
+
+    async fn kick(&mut self, call: Call<'_>) -> Result<()> {
+        let who = call.rest();
+        if self.network.is_none() || self.shared.our_id != room_moderator(&self.room) {
+            status("> only the room's moderator (whoever opened it) can kick people".red());
+            return Ok(());
+        }
+        // by name, or by the short id for people who never set one. those may never have said
+        // anything either, so everyone we're connected to counts as well as everyone with a name
+        let matches: Vec<_> = {
+            let names = self.shared.names.lock().expect("should be able to acquire lock");
+            let neighbors = self.shared.neighbors.lock().expect("should be able to acquire lock");
+            let everyone: HashSet<NodeId> = names.keys().chain(neighbors.iter()).copied().collect();
+            everyone.into_iter()
+                .filter(|id| get_name(&names, *id) == who || id.fmt_short().to_string() == who)
+                .collect()
+        };
+        match matches.as_slice() {
+            [target] => {
+                let message = MinimalMessage::new(MinimalMessageType::Chat(ChatMessage::Kick {
+                    from: self.shared.our_id,
+                    target: *target,
+                }));
+                self.sender.broadcast(&message).await?;
+                status(format!("> asked everyone to kick {who}. this is up to their clients, it can't be enforced").yellow());
+            }
+            [] => status(format!("usage: {}, nobody here is called `{who}`", call.command.usage(&self.shared.command_prefix)).red()),
+            _ => status(format!("> more than one person is called `{who}`, use their short id instead").red()),
+        }
+        Ok(())
+    }
+
+    async fn games(&mut self, _call: Call<'_>) -> Result<()> {
+        let active_games = self.shared.active_games.lock().expect("should be able to acquire lock");
+        if active_games.is_empty() {
+            status("> nobody is playing right now".blue());
+        }
+        for (short_id, game) in active_games.iter() {
+            status(format!("> game {short_id}: {} vs {}, started {} min ago", game.players[0], game.players[1], game.started.elapsed().as_secs() / 60).blue());
+        }
+        Ok(())
+    }
+
+    async fn spectate(&mut self, call: Call<'_>) -> Result<()> {
+        let game = call.args.first().and_then(|id| id.parse::<u64>().ok()).and_then(|short_id| {
+            self.shared.active_games.lock().expect("should be able to acquire lock").get(&short_id).map(|game| (game.game_id, game.player_ids))
+        });
+        match (game, &self.shared.gossip) {
+            (Some((game_id, player_ids)), Some(gossip)) => {
+                status("> spectating, you'll see what happens in the game here".green());
+                tokio::spawn(spectate_game(game_id, player_ids.to_vec(), gossip.clone()));
+            }
+            _ => status(format!("usage: {}, where id is one of the games from {}games", call.command.usage(&self.shared.command_prefix), self.shared.command_prefix).red()),
+        }
+        Ok(())
+    }
+
+    async fn quit(&mut self, _call: Call<'_>) -> Result<()> {
+        self.quitting = true;
+        Ok(())
+    }
+
+    async fn min(&mut self, call: Call<'_>) -> Result<()> {
+        let our_id = self.shared.our_id;
+        let prefix = &self.shared.command_prefix;
+        match call.args.first() {
+            None | Some(&"solo") | Some(&"cancel") => {}
+            Some(_) => {
+                status(format!("usage: {prefix}min to queue for a game, {prefix}min solo to play the bot, {prefix}min cancel to leave the queue (not `{}`)", call.rest()).red());
+                return Ok(());
+            }
+        }
+        if call.args.first() == Some(&"cancel") {
+            let queued = {
+                let mut requester = self.shared.game_request_tracker.lock().expect("should be able to acquire lock");
+                requester.take_if(|(requester, _)| *requester == our_id).is_some()
+            }; // released here, before broadcasting
+            if queued {
+                let message = MinimalMessage::new(MinimalMessageType::Chat(ChatMessage::GameCancel { from: our_id }));
+                self.sender.broadcast(&message).await?;
+                status("> left the minimal queue".green());
+            } else {
+                status("> you aren't in the minimal queue".yellow());
+            }
+            return Ok(());
+        }
+        if !is_interactive() {
+            status("> games need minimal to be run in an interactive terminal, not with piped input or output".red());
+            return Ok(());
+        }
+        if call.args.first() == Some(&"solo") {
+            status("> starting a game against the bot!".green());
+            tokio::spawn(begin_game(rand::random_range(0.0..=1e9), Opponent::Bot, self.our_game_config.clone(), self.shared.clone()));
+            return Ok(());
+        }
+        if self.network.is_none() {
+            // nobody to queue with, so just go straight into a practice game
+            status(format!("> offline, starting a practice game! (or try {prefix}min solo)").green());
+            tokio::spawn(begin_game(rand::random_range(0.0..=1e9), Opponent::Nobody, self.our_game_config.clone(), self.shared.clone()));
+            return Ok(());
+        }
+        // decided with the lock held, but it's let go of before broadcasting anything
+        let queued = {
+            let mut requester = self.shared.game_request_tracker.lock().expect("should be able to acquire lock");
+            match requester.take() {
+                // nobody else has shown up yet, and playing ourselves would be no fun
+                Some((requester_id, config)) if requester_id == our_id => {
+                    *requester = Some((requester_id, config));
+                    status(format!("> you are already in the minimal queue, {prefix}min cancel to leave it").yellow());
+                    false
+                }
+                Some((other_requester, their_config)) => {
+                    let game_id = rand::random_range(0.0..=1e9);
+                    // experimental settings only count if both of us asked for them
+                    let config = if their_config == self.our_game_config { self.our_game_config.clone() } else {
+                        status("> you and your opponent picked different game settings, using the defaults".yellow());
+                        GameConfig::default()
+                    };
+                    // the queue has been emptied by `take`
+                    status("> ok, starting a game!".green());
+                    let joined = Arc::new(tokio::sync::Notify::new());
+                    tokio::spawn(announce_game_start(self.sender.clone(), our_id, other_requester, game_id, config.clone(), joined.clone()));
+                    tokio::spawn(begin_game(game_id, Opponent::Player { id: other_requester, bootstrap: vec![], joined: Some(joined) }, config, self.shared.clone()));
+                    false
+                }
+                None => {
+                    *requester = Some((our_id, self.our_game_config.clone())); // we are requesting
+                    true
+                }
+            }
+        }; // released here
+        if queued {
+            let message = MinimalMessage::new(MinimalMessageType::Chat(ChatMessage::GameRequest {
+                from: our_id,
+                config: self.our_game_config.clone(),
+            }));
+            self.sender.broadcast(&message).await?;
+            status("> joined the minimal queue!".green());
+        }
+        Ok(())
+    }
+}
+
+// the `--gossip-*` knobs. anything left as None keeps iroh-gossip's own default
+#[derive(Debug, Clone, Default)]
+struct GossipTuning {
+    active_view: Option<usize>,
+    passive_view: Option<usize>,
+    max_message_size: Option<usize>,
+}
+
+impl GossipTuning {
+    fn builder(&self) -> iroh_gossip::net::Builder {
+        let mut membership = HyparviewConfig::default();
+        if let Some(active_view) = self.active_view {
+            membership.active_view_capacity = active_view;
+        }
+        if let Some(passive_view) = self.passive_view {
+            membership.passive_view_capacity = passive_view;
+        }
+        let builder = Gossip::builder().membership_config(membership);
+        match self.max_message_size {
+            Some(size) => builder.max_message_size(size),
+            None => builder,
+        }
+    }
+}
+
+// bring up an endpoint for `secret_key` with gossip and a router on top, and wait until it's online
+// `local` is where to bind in `--local` mode, which leaves out the relays and n0 discovery entirely
+async fn start_network(secret_key: SecretKey, tuning: &GossipTuning, local: Option<SocketAddrV4>) -> Result<Network> {
+    let discovery = StaticProvider::new();
+    let builder = Endpoint::builder()
+        .add_discovery(discovery.clone())
+        .secret_key(secret_key);
+    let builder = match local {
+        Some(addr) => builder.relay_mode(RelayMode::Disabled).bind_addr_v4(addr),
+        None => builder.discovery_n0(),
+    };
+    let endpoint = builder
+        .bind().await
+        .map_err(|e| MinimalError::Network(format!("couldn't open a socket: {e}")))?;
+
+    let gossip = tuning.builder().spawn(endpoint.clone());
+
+    let router = Router::builder(endpoint.clone())
+        .accept(iroh_gossip::ALPN, gossip.clone())
+        .spawn();
+
+    // there's no relay to wait for in local mode, the socket being open is as online as it gets
+    if local.is_none() {
+        status("> connecting to the network...".blue().dim());
+        let wait_for_online = endpoint.online();
+        if tokio::time::timeout(Duration::from_secs(connection_timeout()), wait_for_online).await.is_err() {
+            return Err(MinimalError::Network(format!("couldn't get online within {} seconds", connection_timeout())).into());
+        }
+    }
+    // create an arc to store the gossip because we may need to use it when starting a game
+    Ok(Network { endpoint, discovery, gossip: Arc::new(gossip), router, local: local.is_some() })
+}
+
+// the relay we tell others to reach us through. it can be missing for a moment right after starting,
+// or for good in `--local` mode
+fn show_relays(network: &Network) {
+    if network.local {
+        status("> no relays in local mode, everything goes over localhost".blue());
+        return;
+    }
+    match network.endpoint.node_addr().relay_url {
+        Some(relay_url) => status(format!("> relay: {relay_url}").blue()),
+        None => status("> no relay assigned yet".yellow()),
+    }
+}
+
+// hosts in `--local` mode listen on a port picked from the room name, so joiners on the same
+// computer know where to find them without asking anyone. different rooms mostly get different ports
+const LOCAL_BASE_PORT: u16 = 47800;
+
+fn local_port(room: &str) -> u16 {
+    let topic = room_topic(room);
+    LOCAL_BASE_PORT + u16::from_le_bytes([topic.as_bytes()[0], topic.as_bytes()[1]]) % 1000
+}
+
+// where to bind with `--local`: the room's port for its host, any port for everyone else
+fn local_bind(local: bool, is_host: bool, room: &str) -> Option<SocketAddrV4> {
+    local.then(|| SocketAddrV4::new(Ipv4Addr::LOCALHOST, if is_host { local_port(room) } else { 0 }))
+}
+
+// everything needed to join a room, from whoever is hosting it. rooms can be found by name alone, but
+// the host's address means not having to guess its relay, and a room nobody can guess the name of is
+// only reachable through its ticket
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Ticket {
+    room: String,
+    // whether the room talks `--compact`, which changes its topic
+    compact: bool,
+    // whether it needs `--password`. the password itself stays out of the ticket, tickets get pasted around
+    #[serde(default)]
+    password: bool,
+    host: NodeAddr,
+}
+
+const TICKET_PREFIX: &str = "minimal";
+
+impl Ticket {
+    // json in lowercase base32, which survives being pasted anywhere and double clicks as one word
+    fn encode(&self) -> String {
+        let json = serde_json::to_vec(self).expect("serde_json::to_vec is infallible");
+        format!("{TICKET_PREFIX}{}", data_encoding::BASE32_NOPAD.encode(&json).to_ascii_lowercase())
+    }
+
+    fn decode(ticket: &str) -> Result<Self> {
+        let invalid = |reason: &str| MinimalError::Room(format!("that ticket isn't valid ({reason}), ask the host for it again"));
+        let encoded = ticket.trim().strip_prefix(TICKET_PREFIX).ok_or_else(|| invalid("it should start with `minimal`"))?;
+        let json = data_encoding::BASE32_NOPAD.decode(encoded.to_ascii_uppercase().as_bytes()).map_err(|_| invalid("it got cut off or mangled"))?;
+        let ticket: Ticket = serde_json::from_slice(&json).map_err(|_| invalid("it's from a different version"))?;
+        if ticket.password && room_key().is_none() {
+            return Err(MinimalError::Room("that room has a password, pass it with --password".to_string()).into());
+        }
+        // only the room's own host key can be its host, anything else would be someone pretending.
+        // a wrong password ends up here too, it makes for a different host key
+        if ticket.host.node_id != host_secret_key_with(&ticket.room, ticket.compact).public() {
+            let reason = if ticket.password { "the host in it doesn't match the room, check the password" } else { "the host in it doesn't match the room" };
+            return Err(invalid(reason).into());
+        }
+        Ok(ticket)
+    }
+
+    // where to find the host, for as long as we're in the room the ticket is for
+    fn host_in(&self, room: &str) -> Option<NodeAddr> {
+        (self.room == room).then(|| self.host.clone())
+    }
+
+    // only the host can hand out a ticket, nobody else's address gets you into the room
+    fn for_host(room: &str, endpoint: &Endpoint) -> Option<Self> {
+        let host = endpoint.node_addr();
+        (host.node_id == room_moderator(room)).then(|| Ticket { room: room.to_string(), compact: COMPACT.load(Ordering::Relaxed), password: room_key().is_some(), host })
+    }
+}
+
+// what someone else would run to end up in `room`. with a ticket that's all they need, otherwise
+// rooms are found by name
+fn join_command(room: &str, ticket: Option<&Ticket>) -> String {
+    if let Some(ticket) = ticket {
+        return format!("minimal join {}", ticket.encode());
+    }
+    let compact = if COMPACT.load(Ordering::Relaxed) { " --compact" } else { "" };
+    if room.is_empty() { format!("minimal{compact} join") } else { format!("minimal --room {room:?}{compact} join") }
+}
+
+// the clipboard tools we know about, tried in order. going through them instead of a clipboard crate
+// keeps the dependencies down, and a headless box without any of them just falls back to printing
+const CLIPBOARD_COMMANDS: &[&[&str]] = &[&["pbcopy"], &["wl-copy"], &["xclip", "-selection", "clipboard"], &["xsel", "--clipboard", "--input"], &["clip.exe"]];
+
+fn copy_to_clipboard(text: &str) -> bool {
+    CLIPBOARD_COMMANDS.iter().any(|command| {
+        let Ok(mut child) = std::process::Command::new(command[0])
+            .args(&command[1..])
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn() else { return false };
+        let written = child.stdin.take().is_some_and(|mut input| input.write_all(text.as_bytes()).is_ok());
+        child.wait().is_ok_and(|exit| exit.success()) && written
+    })
+}
+
+fn copy_join_command(room: &str, ticket: Option<&Ticket>) {
+    let command = join_command(room, ticket);
+    if copy_to_clipboard(&command) {
+        status(format!("> copied `{command}` to the clipboard, send it to whoever should join").green());
+    } else {
+        status(format!("> couldn't reach the clipboard, here it is to copy by hand: {command}").yellow());
+    }
+}
+
+// find the host of `room` through our relay, or on its fixed port in `--local` mode, and join its topic
+async fn join_room(room: &str, network: &Network, ticket_host: Option<NodeAddr>) -> Result<(GossipSender, GossipReceiver)> {
+    // mimic the logic used to generate the host key
+    let host_addr = NodeAddr::new(host_secret_key(room).public());
+    let host_addr = if let Some(ticket_host) = ticket_host {
+        // the ticket says where the host actually is, no need to assume it shares our relay
+        ticket_host
+    } else if network.local {
+        host_addr.with_direct_addresses([SocketAddr::from((Ipv4Addr::LOCALHOST, local_port(room)))])
+    } else {
+        host_addr.with_relay_url(network.endpoint.node_addr().relay_url.ok_or(
+            MinimalError::Network("node should have a relay_url".to_string())
+        )?)
+    };
+    network.discovery.add_node_info(host_addr.clone());
+    let joined = tokio::time::timeout(
+        Duration::from_secs(connection_timeout()),
+        network.gossip.subscribe_and_join(room_topic(room), vec![host_addr.node_id])
+    ).await;
+    match joined {
+        Ok(topic) => Ok(topic?.split()),
+        Err(_) => Err(MinimalError::Network(
+            format!("couldn't connect to host within {} seconds, maybe try `cargo run open` to start a server?", connection_timeout())
+        ).into()),
+    }
+}
+
+// set up the room's topic as its host. unlike joining there is nobody to wait for, so this only
+// has to get the topic going, and a room with nobody in it yet is not a failure
+async fn host_room(room: &str, gossip: &Gossip, attempts: u32) -> Result<(GossipSender, GossipReceiver)> {
+    let mut delay = Duration::from_secs(1);
+    for attempt in 1..=attempts {
+        let subscribed = tokio::time::timeout(
+            Duration::from_secs(connection_timeout()),
+            gossip.subscribe(room_topic(room), vec![])
+        ).await;
+        match subscribed {
+            Ok(Ok(topic)) => return Ok(topic.split()),
+            Ok(Err(e)) => status(format!("> couldn't open {} ({e}), attempt {attempt} of {attempts}", describe_room(room)).yellow()),
+            Err(_) => status(format!("> gossip didn't answer within {} seconds, attempt {attempt} of {attempts}", connection_timeout()).yellow()),
+        }
+        if attempt < attempts {
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+        }
+    }
+    Err(MinimalError::Network(format!("couldn't open {} after {attempts} attempts", describe_room(room))).into())
+}
+
+const ABOUT_ME_ATTEMPTS: u32 = 3; // how many times the startup name is sent, in case the first ones reach nobody
+
+// announce our name once somebody can hear it. gossip doesn't hold on to messages, so a host
+// broadcasting right at startup (before anyone joined) would be talking to an empty room.
+// it's repeated a few times with backoff since a brand new neighbor may not be relaying yet.
+// without a name there's still `Joined`, a name already shows up as "joined as"
+async fn announce_presence(sender: Outbox, mut neighbor_count: tokio::sync::watch::Receiver<usize>, from: NodeId, name: Option<String>) -> Result<()> {
+    let mut delay = Duration::from_secs(1);
+    for _attempt in 0..ABOUT_ME_ATTEMPTS {
+        neighbor_count.wait_for(|count| *count > 0).await?;
+        let message = MinimalMessage::new(MinimalMessageType::Chat(match &name {
+            Some(name) => ChatMessage::AboutMe { from, name: name.clone() },
+            None => ChatMessage::Joined { from },
+        }));
+        sender.broadcast(&message).await?;
+        tokio::time::sleep(delay).await;
+        delay *= 2;
+    }
+    Ok(())
+}
+
+fn report_step(step: &str, outcome: &Result<String, String>, elapsed: Duration) {
+    match outcome {
+        Ok(detail) => println!("{}", format!("> [ok]   {step} ({} ms) {detail}", elapsed.as_millis()).green()),
+        Err(detail) => println!("{}", format!("> [fail] {step} ({} ms) {detail}", elapsed.as_millis()).red()),
+    }
+}
+
+// go through the same steps as joining a room, but say how each one went instead of chatting.
+// returns whether every step passed so `main` can pick an exit code
+async fn doctor(room: &str) -> Result<()> {
+    println!("{}", "> running connectivity checks...".blue().dim());
+    let timeout = Duration::from_secs(connection_timeout());
+
+    // step 1: can we even make an endpoint? this fails on socket/permission problems
+    let started = Instant::now();
+    let discovery = StaticProvider::new();
+    let bound = Endpoint::builder()
+        .discovery_n0()
+        .add_discovery(discovery.clone())
+        .secret_key(SecretKey::generate(&mut rand::rng()))
+        .bind().await;
+    let outcome = bound.as_ref().map(|endpoint| format!("as {}", endpoint.node_id().fmt_short())).map_err(|e| e.to_string());
+    report_step("create endpoint", &outcome, started.elapsed());
+    let Ok(endpoint) = bound else {
+        return Err(MinimalError::Network("couldn't even make an endpoint, see above.".to_string()).into());
+    };
+    let gossip = Gossip::builder().spawn(endpoint.clone());
+    let router = Router::builder(endpoint.clone())
+        .accept(iroh_gossip::ALPN, gossip.clone())
+        .spawn();
+
+    // step 2: getting online needs DNS and at least one relay to answer
+    let started = Instant::now();
+    let outcome = match tokio::time::timeout(timeout, endpoint.online()).await {
+        Ok(()) => Ok(String::new()),
+        Err(_) => Err(format!("not online after {} seconds, check DNS and your firewall", timeout.as_secs())),
+    };
+    report_step("go online", &outcome, started.elapsed());
+    let mut healthy = outcome.is_ok();
+
+    // step 3: joiners borrow our relay url to find the host, so we need one
+    let started = Instant::now();
+    let relay_url = endpoint.node_addr().relay_url;
+    let outcome = relay_url.as_ref().map(|url| url.to_string()).ok_or("no relay url was assigned".to_string());
+    report_step("resolve relay", &outcome, started.elapsed());
+    healthy &= outcome.is_ok();
+
+    // step 4: actually try to reach the host through gossip, same as `Command::Join`
+    let started = Instant::now();
+    let outcome = match relay_url {
+        Some(relay_url) => {
+            let host_addr = NodeAddr::new(host_secret_key(room).public()).with_relay_url(relay_url);
+            discovery.add_node_info(host_addr.clone());
+            match tokio::time::timeout(timeout, gossip.subscribe_and_join(room_topic(room), vec![host_addr.node_id])).await {
+                Ok(Ok(_)) => Ok(format!("reached host {}", host_addr.node_id.fmt_short())),
+                Ok(Err(e)) => Err(e.to_string()),
+                Err(_) => Err(format!("host didn't answer within {} seconds, is anyone running `open`?", timeout.as_secs())),
+            }
+        }
+        None => Err("skipped, there is no relay to reach the host through".to_string()),
+    };
+    report_step("join chat room", &outcome, started.elapsed());
+    healthy &= outcome.is_ok();
+
+    router.shutdown().await?;
+    if !healthy {
+        // exits through `main` like any other network trouble, so scripts get the same exit code
+        return Err(MinimalError::Network("some checks failed, see above.".to_string()).into());
+    }
+    println!("{}", "> everything looks fine!".green().bold());
+    Ok(())
+}
+
+// everything below is sent as json, or as postcard with `--compact` (see `encode`). json names every
+// variant and field, so those names are the wire format: renaming one breaks talking to older clients
+// on the same topic, and a new field needs `#[serde(default)]` so messages from them still parse.
+// postcard names nothing and just reads fields in order, so `#[serde(default)]` doesn't help it and
+// even an added field is read as garbage by the other side. anything that changes shape, including a
+// new defaulted field, needs MINIMAL_VERSION bumped, which moves both topics
+#[derive(Debug, Serialize, Deserialize)]
+struct MinimalMessage {
+    body: MinimalMessageType,
+    nonce: [u8; 16],
+}
+
+// what actually goes over gossip. gossip only says which neighbor passed a message along, not who wrote
+// it, so every message is signed by its author. `message` is the encoded `MinimalMessage` exactly as it
+// was signed, and any `from` inside it has to match `from` here (see `MinimalMessage::sent_by`)
+#[derive(Debug, Serialize, Deserialize)]
+struct SignedMessage {
+    from: NodeId,
+    #[serde(serialize_with = "wire_bytes::serialize_text", deserialize_with = "wire_bytes::deserialize_text")]
+    message: Vec<u8>,
+    #[serde(serialize_with = "wire_bytes::serialize_hex", deserialize_with = "wire_bytes::deserialize_hex")]
+    signature: Vec<u8>,
+}
+
+// json writes a Vec<u8> as an array of numbers, three or four times its size, which would eat most of
+// `MAX_MESSAGE_BYTES`. with json the signed message is json itself, so it goes in as a string, and the
+// signature goes in as hex. postcard is binary anyway and keeps both as they are
+mod wire_bytes {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize_text<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        match std::str::from_utf8(bytes) {
+            Ok(text) if serializer.is_human_readable() => serializer.serialize_str(text),
+            _ => serializer.serialize_bytes(bytes),
+        }
+    }
+
+    pub fn deserialize_text<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        if deserializer.is_human_readable() {
+            Ok(String::deserialize(deserializer)?.into_bytes())
+        } else {
+            Vec::deserialize(deserializer)
+        }
+    }
+
+    pub fn serialize_hex<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&data_encoding::HEXLOWER.encode(bytes))
+        } else {
+            serializer.serialize_bytes(bytes)
+        }
+    }
+
+    pub fn deserialize_hex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        if deserializer.is_human_readable() {
+            data_encoding::HEXLOWER_PERMISSIVE.decode(String::deserialize(deserializer)?.as_bytes()).map_err(D::Error::custom)
+        } else {
+            Vec::deserialize(deserializer)
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum MinimalMessageType {
+    Chat(ChatMessage),
+    Game(GameMessage),
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 enum ChatMessage {
     AboutMe { from: NodeId, name: String },
-    Message { from: NodeId, text: String },
-    GameRequest { from: NodeId },
-    GameStart { from: NodeId, orig_sender: NodeId, game_id: f64 },
+    // `sent_at` is unix millis by the sender's clock, older clients don't send it
+    Message { from: NodeId, text: String, #[serde(default)] sent_at: u64 },
+    // older clients don't send a config, they get the default game
+    GameRequest { from: NodeId, #[serde(default)] config: GameConfig },
+    GameStart { from: NodeId, orig_sender: NodeId, game_id: f64, #[serde(default)] config: GameConfig },
+    Reaction { from: NodeId, target_nonce: [u8; 16], emoji: String },
+    // sent by the host to ask everyone to ignore `target`, and `target` to leave. gossip can't actually
+    // throw anyone out, so this only works on clients that play along. only listened to when the message
+    // is signed by the moderator, and since that key comes from the room name it keeps out casual
+    // spoofing rather than anyone determined
+    Kick { from: NodeId, target: NodeId },
+    // the host catching a new joiner up on what was said before they arrived, oldest first
+    Backlog { from: NodeId, messages: Vec<BacklogEntry> },
+    // change or take back one of your own messages. only honored when `from` matches who sent it, though
+    // since every message is signed by whoever sent it (see `SignedMessage`), nobody else can do it for them
+    Edit { from: NodeId, target_nonce: [u8; 16], new_text: String },
+    Delete { from: NodeId, target_nonce: [u8; 16] },
+    GameOver { from: NodeId, game_id: f64 },
+    // the host saying where it can be reached now, after its relay changed. only listened to from the moderator
+    HostAddr { from: NodeId, addr: NodeAddr },
+    // taking back a `GameRequest` nobody has answered yet
+    GameCancel { from: NodeId },
+    // a line about what the room is for, set by the host with /settopic. nothing to do with the gossip
+    // `TopicId`, and like `Backlog` it's only listened to from the moderator
+    RoomTopic { from: NodeId, text: String },
+    // a file someone has, by its blake3 hash. the data itself never goes over gossip, this is only so
+    // people can see it exists. fetching it as an iroh blob would be the next step
+    Attachment { from: NodeId, name: String, hash: [u8; 32] },
+    // `/me`, shown as `* name text` instead of as something they said
+    Action { from: NodeId, text: String },
+    // coming and going. someone with a name says `AboutMe` instead of `Joined`, see `announce_presence`
+    Joined { from: NodeId },
+    Left { from: NodeId },
+}
+
+impl ChatMessage {
+    fn sender(&self) -> NodeId {
+        match self {
+            ChatMessage::AboutMe { from, .. }
+            | ChatMessage::Message { from, .. }
+            | ChatMessage::GameRequest { from, .. }
+            | ChatMessage::GameStart { from, .. }
+            | ChatMessage::Reaction { from, .. }
+            | ChatMessage::Kick { from, .. }
+            | ChatMessage::Backlog { from, .. }
+            | ChatMessage::Edit { from, .. }
+            | ChatMessage::Delete { from, .. }
+            | ChatMessage::GameOver { from, .. }
+            | ChatMessage::GameCancel { from }
+            | ChatMessage::HostAddr { from, .. }
+            | ChatMessage::RoomTopic { from, .. }
+            | ChatMessage::Attachment { from, .. }
+            | ChatMessage::Action { from, .. }
+            | ChatMessage::Joined { from }
+            | ChatMessage::Left { from } => *from,
+        }
+    }
+}
+
+// the live network handles. `main` doesn't have any of these in `--offline` mode
+struct Network {
+    endpoint: Endpoint,
+    discovery: StaticProvider,
+    gossip: Arc<Gossip>,
+    router: Router,
+    // `--local`, no relays so hosts are found by their fixed port instead
+    local: bool,
+}
+
+// where outgoing messages go, and the key they're signed with. offline there's nobody to send to, so
+// broadcasting does nothing
+#[derive(Debug, Clone)]
+enum Outbox {
+    // the key is big next to `Offline`, and every clone of the outbox can share the one copy
+    Gossip(GossipSender, Arc<SecretKey>),
+    Offline,
+}
+
+impl Outbox {
+    fn gossip(sender: GossipSender, endpoint: &Endpoint) -> Self {
+        Outbox::Gossip(sender, Arc::new(endpoint.secret_key().clone()))
+    }
+
+    // whether `message` is small enough that anyone will take it, see `MAX_MESSAGE_BYTES`
+    fn fits(&self, message: &MinimalMessage) -> bool {
+        match self {
+            Outbox::Gossip(_, key) => message.to_signed(key).len() <= message_limit(),
+            Outbox::Offline => true,
+        }
+    }
+
+    async fn broadcast(&self, message: &MinimalMessage) -> Result<()> {
+        if let Outbox::Gossip(sender, key) = self {
+            sender.broadcast(message.to_signed(key).into()).await?;
+        }
+        Ok(())
+    }
+}
+
+// everything the input loop and the subscribe loop both need a handle on
+#[derive(Clone)]
+struct SharedState {
+    our_id: PublicKey,
+    // see `room_moderator`
+    moderator: PublicKey,
+    // the name we go by
+    our_name: Arc<Mutex<String>>,
+    stats_path: PathBuf,
+    // missing in `--offline` mode, along with everything else network related
+    gossip: Option<Arc<Gossip>>,
+    endpoint: Option<Endpoint>,
+    discovery: Option<StaticProvider>,
+    chat_sender: Outbox,
+    // feeds lines into the input loop as if they were typed, so background tasks can run commands
+    commands: tokio::sync::mpsc::Sender<String>,
+    // from the config, checked to not be empty
+    command_prefix: String,
+    // the mapping between `NodeId`s and names, filled in by the subscribe loop
+    names: Arc<Mutex<HashMap<PublicKey, String>>>,
+    // who we're directly connected to on the chat topic right now, for /list. also kept by the subscribe loop
+    neighbors: Arc<Mutex<HashSet<NodeId>>>,
+    // variable to keep track of game requests
+    game_request_tracker: Arc<Mutex<Option<(PublicKey, GameConfig)>>>,
+    // nonce of the newest message from someone else, this is what /react reacts to
+    last_message: Arc<Mutex<Option<[u8; 16]>>>,
+    // games in this room that nobody has said are over yet, by the short id shown in /games
+    active_games: Arc<Mutex<BTreeMap<u64, ActiveGame>>>,
+    // how many messages reactions can point back to, from the config
+    history_limit: usize,
+    // the last few messages in the room, ours included, for the host to replay to joiners
+    backlog: Arc<Mutex<VecDeque<BacklogEntry>>>,
+    backlog_size: usize,
+    // everything said since we got here, up to `history_limit` of it, for /history. unlike the backlog
+    // this never goes anywhere, so it can be a lot longer
+    scrollback: Arc<Mutex<VecDeque<BacklogEntry>>>,
+    // `--log`, opened once at startup
+    transcript: Option<Arc<Mutex<fs::File>>>,
+    // the host's /settopic line, already cleaned up
+    topic_line: Arc<Mutex<Option<String>>>,
+    // from `--allow`, who the host lets in. empty means everyone
+    allowlist: Arc<HashSet<NodeId>>,
+    // when we last typed anything, so messages that came in while we were away can be marked
+    last_active: Arc<Mutex<Instant>>,
+}
+
+impl SharedState {
+    // whether we're hosting with `--allow` and `id` isn't on it
+    fn turns_away(&self, id: NodeId) -> bool {
+        self.our_id == self.moderator && !self.allowlist.is_empty() && !self.allowlist.contains(&id)
+    }
+}
+
+// the host's side of `--allow`: a kick, same as /kick sends, for someone who isn't on the list. only
+// called while hosting, so it goes out signed with the host key
+async fn turn_away(shared: &SharedState, target: NodeId) -> Result<()> {
+    let message = MinimalMessage::new(MinimalMessageType::Chat(ChatMessage::Kick { from: shared.our_id, target }));
+    shared.chat_sender.broadcast(&message).await?;
+    status(format!("> turned away {}, they aren't on the --allow list", target.fmt_short()).yellow());
+    Ok(())
+}
+
+// one message as replayed in a `Backlog`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BacklogEntry {
+    from: NodeId,
+    name: String,
+    text: String,
+    // unix millis of when we saw it, older hosts don't send this
+    #[serde(default)]
+    at: u64,
+}
+
+// whatever a host says, a backlog is never shown with more than this many messages, or longer ones than this
+const MAX_BACKLOG_MESSAGES: usize = 50;
+const MAX_BACKLOG_TEXT_CHARS: usize = 500;
+
+// one line of the `--log` transcript, without any styling. flushed straight away so a crash doesn't take
+// the last few lines with it, and a write that fails is only a missing line
+fn transcribe(shared: &SharedState, line: &str) {
+    let Some(transcript) = &shared.transcript else { return };
+    let mut file = transcript.lock().expect("should be able to acquire lock");
+    let _ = writeln!(file, "{} {line}", Local::now().format("%Y-%m-%d %H:%M:%S"));
+    let _ = file.flush();
+}
+
+// every chat line goes through here, so it's also where /history gets its copy
+fn remember_for_backlog(shared: &SharedState, entry: BacklogEntry) {
+    let mut scrollback = shared.scrollback.lock().expect("should be able to acquire lock");
+    scrollback.push_back(entry.clone());
+    while scrollback.len() > shared.history_limit {
+        scrollback.pop_front();
+    }
+    let mut backlog = shared.backlog.lock().expect("should be able to acquire lock");
+    backlog.push_back(entry);
+    while backlog.len() > shared.backlog_size {
+        backlog.pop_front();
+    }
+}
+
+// replay the backlog to the room. gossip can't send to just the new neighbor, so everyone gets it and
+// the ones who are already caught up ignore it
+async fn send_backlog(shared: &SharedState) -> Result<()> {
+    let mut messages: Vec<_> = shared.backlog.lock().expect("should be able to acquire lock").iter().cloned().collect();
+    // whatever doesn't fit is left out, oldest first, since the newest lines are what a joiner needs most
+    loop {
+        if messages.is_empty() {
+            return Ok(());
+        }
+        let message = MinimalMessage::new(MinimalMessageType::Chat(ChatMessage::Backlog { from: shared.our_id, messages: messages.clone() }));
+        if shared.chat_sender.fits(&message) {
+            return shared.chat_sender.broadcast(&message).await;
+        }
+        messages.remove(0);
+    }
+}
+
+const MAX_TOPIC_LINE_CHARS: usize = 120;
+
+fn clean_topic_line(text: &str) -> String {
+    sanitize(text.trim()).chars().take(MAX_TOPIC_LINE_CHARS).collect()
+}
+
+const MAX_ATTACHMENT_NAME_CHARS: usize = 64;
+
+// just the file's own name, whoever sent it. a path would say more about the sender's disk than the file
+fn clean_attachment_name(name: &str) -> String {
+    let name = name.rsplit(['/', '\\']).next().unwrap_or_default();
+    let name: String = sanitize(name.trim()).chars().take(MAX_ATTACHMENT_NAME_CHARS).collect();
+    if name.is_empty() { "(unnamed)".to_string() } else { name }
+}
+
+// the file is read a piece at a time, so sharing something big doesn't mean holding all of it in memory.
+// it's blocking io, so it runs off the async threads
+async fn hash_file(path: PathBuf) -> std::io::Result<[u8; 32]> {
+    tokio::task::spawn_blocking(move || {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update_reader(fs::File::open(path)?)?;
+        Ok(*hasher.finalize().as_bytes())
+    })
+    .await?
+}
+
+// enough of the hash to tell two files apart by eye
+fn short_hash(hash: &[u8; 32]) -> String {
+    format!("{}…", data_encoding::HEXLOWER.encode(&hash[..6]))
+}
+
+// links are underlined so they stand out from the rest of the message. split on single spaces so the
+// spacing someone typed comes through as it was
+fn style_chat_text(text: &str) -> String {
+    text.split(' ')
+        .map(|word| {
+            if word.starts_with("https://") || word.starts_with("http://") {
+                word.cyan().underlined().to_string()
+            } else {
+                word.cyan().to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// the topic line goes out to everyone the same way the backlog does, whenever someone new shows up
+async fn send_topic_line(shared: &SharedState) -> Result<()> {
+    let Some(text) = shared.topic_line.lock().expect("should be able to acquire lock").clone() else {
+        return Ok(());
+    };
+    let message = MinimalMessage::new(MinimalMessageType::Chat(ChatMessage::RoomTopic { from: shared.our_id, text }));
+    shared.chat_sender.broadcast(&message).await
+}
+
+// someone already in the queue asked before a joiner got here, so the host passes it along to them
+async fn send_game_request(shared: &SharedState) -> Result<()> {
+    let Some((from, config)) = shared.game_request_tracker.lock().expect("should be able to acquire lock").clone() else {
+        return Ok(());
+    };
+    let message = MinimalMessage::new(MinimalMessageType::Chat(ChatMessage::GameRequest { from, config }));
+    shared.chat_sender.broadcast(&message).await
+}
+
+// how long `--quickplay` listens after connecting, so the host can say whether someone is already waiting
+const QUICKPLAY_SETTLE: Duration = Duration::from_secs(2);
+
+// `--quickplay`: type /min for the user as soon as there's anyone to play with
+async fn quickplay(commands: tokio::sync::mpsc::Sender<String>, mut neighbor_count: tokio::sync::watch::Receiver<usize>, online: bool, prefix: String) -> Result<()> {
+    if online {
+        if *neighbor_count.borrow() == 0 {
+            status("> quickplay: waiting for someone else to show up...".blue().dim());
+        }
+        neighbor_count.wait_for(|count| *count > 0).await?;
+        status("> quickplay: checking if anyone is already waiting for a game...".blue().dim());
+        tokio::time::sleep(QUICKPLAY_SETTLE).await;
+    }
+    commands.send(format!("{prefix}min")).await?;
+    Ok(())
+}
+
+struct ActiveGame {
+    game_id: f64,
+    player_ids: [PublicKey; 2],
+    players: [String; 2],
+    started: Instant,
+}
+
+// `GameOver` is best effort, so games that somehow never reported back get dropped after a while
+const ACTIVE_GAME_EXPIRY: Duration = Duration::from_secs(2 * 60 * 60);
+
+// game ids are random floats below 1e9, the whole part is plenty to tell them apart and easier to type
+fn short_game_id(game_id: f64) -> u64 {
+    game_id.trunc() as u64
+}
+
+// anything else is dropped on arrival, so nobody can paste a wall of text in as a "reaction"
+const REACTIONS: &[&str] = &["👍", "👎", "😂", "❤️", "🎉", "😮"];
+
+// a message we've shown recently, kept around so reactions to it can say what they're about
+struct RecentMessage {
+    author_id: NodeId,
+    author: String,
+    text: String,
+    reactions: BTreeMap<String, usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum GameMessage {
+    Aborted {},
+    // a component taken from the vbox. `seq` counts up from 0 for each player so the other side can ack it,
+    // and play it on their copy of our board in the same order we did. `Refund` and `Craft` share the count
+    PlayComponent { game_id: f64, seq: u64, index: usize },
+    Refund { game_id: f64, seq: u64 },
+    Craft { game_id: f64, seq: u64 },
+    Ack { game_id: f64, seq: u64 },
+    // agreeing on the board. whoever picks `game_id` could keep picking until the vbox suits them, so the
+    // seed comes from both players instead: each sends `Commit` with the blake3 hash of a random secret,
+    // sends `Reveal` with the secret itself only once it has the other's commitment, and checks the
+    // other's secret against their commitment. neither side knows the other's secret before it is bound
+    // to its own, so neither can steer the result. both are repeated until heard since gossip can drop them
+    Commit { game_id: f64, commitment: [u8; 32] },
+    Reveal { game_id: f64, secret: [u8; 32] },
+    // conceding, so `from` loses and the other player wins. `Aborted` is for calling a game off with no winner
+    Surrender { game_id: f64, from: NodeId },
+    // `from`'s whole board, sent whenever they craft something so spectators can follow along
+    SyncState { game_id: f64, from: NodeId, state: min::GameSnapshot },
+}
+
+// how a game ended for us
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GameOutcome {
+    Won,
+    Lost,
+    // called off, or never got going. nobody won
+    Aborted,
+}
+
+const GAME_JOIN_TIMEOUT: Duration = Duration::from_secs(30); // how long to wait for the opponent on the game topic
+const GAME_START_REPEAT: Duration = Duration::from_secs(2);
+const SEED_TIMEOUT: Duration = Duration::from_secs(30); // how long to wait for the other half of the seed
+const SEED_REPEAT: Duration = Duration::from_secs(1);
+
+// gossip can drop a `GameStart` like anything else, and then only one of us would ever be in the game.
+// so it goes out again until the other player turns up on the game topic, or we stop waiting for them.
+// every repeat is a new message, gossip would throw away an exact copy as already seen
+async fn announce_game_start(sender: Outbox, from: NodeId, orig_sender: NodeId, game_id: f64, config: GameConfig, joined: Arc<tokio::sync::Notify>) -> Result<()> {
+    let started = Instant::now();
+    while started.elapsed() < GAME_JOIN_TIMEOUT {
+        let message = MinimalMessage::new(MinimalMessageType::Chat(ChatMessage::GameStart { from, orig_sender, game_id, config: config.clone() }));
+        sender.broadcast(&message).await?;
+        if tokio::time::timeout(GAME_START_REPEAT, joined.notified()).await.is_ok() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+// the board seed from both halves. xor doesn't care about order, so both players end up with the same one
+fn combine_seeds(ours: &[u8; 32], theirs: &[u8; 32]) -> u64 {
+    let mut combined = [0u8; 8];
+    for (i, byte) in combined.iter_mut().enumerate() {
+        *byte = ours[i] ^ theirs[i];
+    }
+    u64::from_le_bytes(combined)
+}
+
+// run our side of the commit/reveal described on `GameMessage::Commit`, with the opponent's half coming
+// in through `handshake` from the game subscribe loop
+async fn agree_on_seed(game_id: f64, sender: &Outbox, handshake: &mut tokio::sync::mpsc::Receiver<GameMessage>) -> Result<u64> {
+    let secret: [u8; 32] = rand::random();
+    let commitment = *blake3::hash(&secret).as_bytes();
+    let mut their_commitment = None;
+    let started = Instant::now();
+    loop {
+        if started.elapsed() > SEED_TIMEOUT {
+            bail!("your opponent never finished agreeing on a board");
+        }
+        // our commitment goes out every time, in case the first one got dropped
+        sender.broadcast(&MinimalMessage::new(MinimalMessageType::Game(GameMessage::Commit { game_id, commitment }))).await?;
+        if their_commitment.is_some() {
+            sender.broadcast(&MinimalMessage::new(MinimalMessageType::Game(GameMessage::Reveal { game_id, secret }))).await?;
+        }
+        let Ok(received) = tokio::time::timeout(SEED_REPEAT, handshake.recv()).await else { continue };
+        match received {
+            Some(GameMessage::Commit { commitment: theirs, .. }) => their_commitment = Some(theirs),
+            // a reveal before their commitment can't be checked yet, it'll be repeated
+            Some(GameMessage::Reveal { secret: theirs, .. }) => if let Some(their_commitment) = their_commitment {
+                if *blake3::hash(&theirs).as_bytes() != their_commitment {
+                    bail!("your opponent's half of the seed didn't match what they committed to");
+                }
+                // they may still be waiting on our reveal, so say it a few more times on the way out
+                let sender = sender.clone();
+                tokio::spawn(async move {
+                    for _ in 0..3 {
+                        tokio::time::sleep(SEED_REPEAT).await;
+                        sender.broadcast(&MinimalMessage::new(MinimalMessageType::Game(GameMessage::Commit { game_id, commitment }))).await?;
+                        sender.broadcast(&MinimalMessage::new(MinimalMessageType::Game(GameMessage::Reveal { game_id, secret }))).await?;
+                    }
+                    anyhow::Ok(())
+                });
+                return Ok(combine_seeds(&secret, &theirs));
+            },
+            Some(_) => {}
+            None => bail!("the game ended before a board was agreed on"),
+        }
+    }
+}
+
+// our copy of the opponent's board, shared between the game loop that draws it and the game subscribe
+// loop that plays their moves on it. gossip can deliver moves out of order or twice, so they're played
+// by `seq`, and any that turn up early wait in `pending` until the ones before them arrive
+#[derive(Default)]
+struct OpponentBoard {
+    // None until the seed is agreed on, moves that come in before that wait too
+    board: Option<min::MinimalGameState>,
+    next_seq: u64,
+    pending: BTreeMap<u64, min::Move>,
+}
+
+impl OpponentBoard {
+    fn start(&mut self, board: min::MinimalGameState) {
+        self.board = Some(board);
+        self.catch_up();
+    }
+
+    // false if this didn't change the board, because it was a repeat or is still waiting on earlier moves
+    fn receive(&mut self, seq: u64, played: min::Move) -> bool {
+        if seq < self.next_seq { return false; }
+        self.pending.insert(seq, played);
+        self.catch_up()
+    }
+
+    fn catch_up(&mut self) -> bool {
+        let Some(board) = &mut self.board else { return false };
+        let mut changed = false;
+        while let Some(played) = self.pending.remove(&self.next_seq) {
+            // a move that doesn't work on our copy still counts, or every move after it would wait forever
+            changed |= board.apply(played);
+            self.next_seq += 1;
+        }
+        changed
+    }
+}
+
+// an `OpponentBoard` and a way for the game loop to hear that it changed, or that the opponent ended the
+// game, without holding the lock to wait
+struct OpponentView {
+    // who the opponent is. anyone can subscribe to a game topic, so nothing signed by anyone else counts
+    id: NodeId,
+    board: Mutex<OpponentBoard>,
+    // how the game came out for us, if the opponent is the one who ended it. set before `changed` is
+    // notified, so the game loop never misses it the way it could miss something in a full channel
+    ended: Mutex<Option<GameOutcome>>,
+    changed: tokio::sync::Notify,
+}
+
+impl OpponentView {
+    fn new(id: NodeId) -> Self {
+        OpponentView { id, board: Mutex::default(), ended: Mutex::new(None), changed: tokio::sync::Notify::new() }
+    }
+}
+
+// gossip is best effort, so moves get resent until the opponent acks them
+const ACK_TIMEOUT: Duration = Duration::from_secs(2);
+const MOVE_ATTEMPTS: u32 = 4;
+
+// our side of the move sequence: what's been sent and not acked yet, and how many moves we gave up on
+#[derive(Default)]
+struct Outgoing {
+    next_seq: u64,
+    unacked: BTreeMap<u64, UnackedMove>,
+    lost: usize,
+}
+
+struct UnackedMove {
+    message: GameMessage,
+    sent: Instant,
+    attempts: u32,
+}
+
+// at most one move goes out on a game topic per interval. mashing space can outrun that easily, so
+// moves queue up in `send_moves` and go out in order as fast as this allows. none are ever dropped
+const MOVE_SEND_INTERVAL: Duration = Duration::from_millis(50);
+
+// send our moves to the opponent, paced. ends once the game drops its end of `moves` and the queue is empty
+async fn send_moves(game_id: f64, sender: Outbox, outgoing: Arc<Mutex<Outgoing>>, mut moves: tokio::sync::mpsc::Receiver<min::Move>) -> Result<()> {
+    while let Some(played) = moves.recv().await {
+        let play = {
+            let mut outgoing = outgoing.lock().expect("should be able to acquire lock");
+            let seq = outgoing.next_seq;
+            outgoing.next_seq += 1;
+            let play = match played {
+                min::Move::Take(index) => GameMessage::PlayComponent { game_id, seq, index },
+                min::Move::Refund => GameMessage::Refund { game_id, seq },
+                min::Move::Craft => GameMessage::Craft { game_id, seq },
+            };
+            outgoing.unacked.insert(seq, UnackedMove { message: play.clone(), sent: Instant::now(), attempts: 1 });
+            play
+        }; // released here, before any awaiting
+        sender.broadcast(&MinimalMessage::new(MinimalMessageType::Game(play))).await?;
+        tokio::time::sleep(MOVE_SEND_INTERVAL).await;
+    }
+    Ok(())
+}
+
+// everyone on a topic uses the same encoding (see `wire_version`), so there's no guessing which one this is
+fn decode<T: serde::de::DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    let parsed = if COMPACT.load(Ordering::Relaxed) {
+        postcard::from_bytes(bytes).map_err(|e| e.to_string())
+    } else {
+        serde_json::from_slice(bytes).map_err(|e| e.to_string())
+    };
+    parsed.map_err(|e| MinimalError::Protocol(format!("couldn't parse a message: {e}")).into())
+}
+
+fn encode(value: &impl Serialize) -> Vec<u8> {
+    // neither can fail on these types, there are no maps with non-string keys and nothing of unknown length
+    if COMPACT.load(Ordering::Relaxed) {
+        postcard::to_allocvec(value).expect("postcard::to_allocvec can encode any message")
+    } else {
+        serde_json::to_vec(value).expect("serde_json::to_vec is infallible")
+    }
+}
+
+impl MinimalMessage {
+    // the message and who signed it. a signature that doesn't check out is as good as unreadable
+    fn from_signed(bytes: &[u8]) -> Result<(NodeId, Self)> {
+        let signed: SignedMessage = decode(bytes)?;
+        let signature = <[u8; 64]>::try_from(signed.signature.as_slice())
+            .map_err(|_| MinimalError::Protocol("a message's signature is the wrong length".to_string()))?;
+        if signed.from.verify(&signed.message, &Signature::from_bytes(&signature)).is_err() {
+            return Err(MinimalError::Protocol(format!("a message claiming to be signed by {} wasn't", signed.from.fmt_short())).into());
+        }
+        Ok((signed.from, decode(&signed.message)?))
+    }
+    pub fn new(body: MinimalMessageType) -> Self {
+        Self { body, nonce: rand::random(), }
+    }
+    pub fn to_signed(&self, key: &SecretKey) -> Vec<u8> {
+        let message = encode(self);
+        let signature = key.sign(&message).to_bytes().to_vec();
+        encode(&SignedMessage { from: key.public(), message, signature })
+    }
+    // whether whoever signed this is who it says it's from. game messages that don't name anybody still
+    // have to come from a player, the game topic's listeners check that against who they're playing
+    fn sent_by(&self, signer: NodeId) -> bool {
+        match &self.body {
+            MinimalMessageType::Chat(chat_message) => chat_message.sender() == signer,
+            MinimalMessageType::Game(GameMessage::Surrender { from, .. } | GameMessage::SyncState { from, .. }) => *from == signer,
+            MinimalMessageType::Game(_) => true,
+        }
+    }
+}
+
+fn get_name(names: &HashMap<PublicKey, String>, from: PublicKey) -> String {
+    names
+        .get(&from)
+        .map_or_else(|| from.fmt_short().to_string(), String::to_string)
+}
+
+// everything the subscribe loop remembers from one message to the next
+#[derive(Default)]
+struct ReceiveState {
+    // recent messages by nonce, oldest first in `recent_order` so they can be forgotten in order
+    recent_messages: HashMap<[u8; 16], RecentMessage>,
+    recent_order: VecDeque<[u8; 16]>,
+    last_bell: Option<Instant>,
+    // everyone the host has kicked, we stop showing anything from them
+    kicked: HashSet<NodeId>,
+    // a backlog is only for catching up, so only the first one counts and only before anything live shows up
+    caught_up: bool,
+    // gossip never hands us our own messages, so anything else signed with our id means someone else has it too
+    warned_duplicate: bool,
+    // `last_active` as of the last "new messages" line, so there's only one per time away
+    marked_away: Option<Instant>,
+    // who has said `Joined` since they last left, it gets repeated in case nobody heard
+    present: HashSet<NodeId>,
+    // nonces of the last `SEEN_NONCES` messages, oldest first in `seen_order`, so a copy gossip delivers
+    // twice only shows up once
+    seen: HashSet<[u8; 16]>,
+    seen_order: VecDeque<[u8; 16]>,
+    // how much more each peer gets to say right now, see `allow_from`
+    allowance: HashMap<NodeId, RateLimit>,
+}
+
+// a token bucket: `RATE_LIMIT_PER_SEC` messages a second on average, in bursts of up to that many
+const RATE_LIMIT_PER_SEC: f64 = 10.0;
+
+struct RateLimit {
+    tokens: f64,
+    refilled: Instant,
+    // whether we've said they're being dropped, so it's one line per flood and not one per message
+    warned: bool,
+}
+
+// gossip only repeats a message within a short while of the first copy, a thousand covers even a busy room
+const SEEN_NONCES: usize = 1024;
+
+impl ReceiveState {
+    // whether to show anything more from `from` right now. `free` messages don't use anything up, so a
+    // name change still gets through after a burst of chat, but nothing does while someone is flooding
+    fn allow_from(&mut self, from: NodeId, free: bool) -> bool {
+        let now = Instant::now();
+        let limit = self.allowance.entry(from).or_insert(RateLimit { tokens: RATE_LIMIT_PER_SEC, refilled: now, warned: false });
+        limit.tokens = (limit.tokens + now.duration_since(limit.refilled).as_secs_f64() * RATE_LIMIT_PER_SEC).min(RATE_LIMIT_PER_SEC);
+        limit.refilled = now;
+        if limit.tokens >= 1.0 {
+            if !free { limit.tokens -= 1.0; }
+            limit.warned = false;
+            return true;
+        }
+        if !limit.warned {
+            limit.warned = true;
+            status(format!("> {} is sending too fast, skipping some of their messages", from.fmt_short()).yellow());
+        }
+        false
+    }
+
+    // false if this nonce already came through, otherwise it's remembered for next time
+    fn first_sighting(&mut self, nonce: [u8; 16]) -> bool {
+        if !self.seen.insert(nonce) {
+            return false;
+        }
+        self.seen_order.push_back(nonce);
+        if self.seen_order.len() > SEEN_NONCES && let Some(oldest) = self.seen_order.pop_front() {
+            self.seen.remove(&oldest);
+        }
+        true
+    }
+}
+
+// typing nothing for this long counts as being away, and the next message gets a line above it
+const AWAY_AFTER: Duration = Duration::from_secs(5 * 60);
+
+// show one message from the room and update whatever it changes. this doesn't wait on anything, so it
+// can hold the locks the whole way through. returns whether the message was us getting kicked
+fn handle_message(message: MinimalMessage, shared: &SharedState, state: &mut ReceiveState) -> Result<bool> {
+    let ReceiveState { recent_messages, recent_order, last_bell, kicked, caught_up, warned_duplicate, marked_away, present, .. } = state;
+    let mut kicked_out = false;
+    let mut names = shared.names.lock().expect("should be able to acquire lock");
+    if let MinimalMessageType::Chat(chat_message) = message.body {
+        if chat_message.sender() == shared.our_id && !*warned_duplicate {
+            // hosts all take the same key for a room, and joiners using the same config share its saved key
+            *warned_duplicate = true;
+            status("> someone else is using your identity, probably also hosting this room or running with your config. nobody can tell you apart, so names, kicks and game stats will get mixed up".red());
+            status(format!("> hosts can quit and run `minimal open --new-identity`, anyone else can {}rekey or give the other copy a --profile", shared.command_prefix).red());
+        }
+        if kicked.contains(&chat_message.sender()) { return Ok(false); }
+        match chat_message {
+            ChatMessage::AboutMe { from, name } => {
+                // other people's names get the same cleaning as ours, they end up in everyone's terminal
+                let name = clean_nickname(&name);
+                if name.is_empty() { return Ok(false); }
+                // names get repeated at startup in case nobody heard them, no need to say it twice
+                if names.get(&from) == Some(&name) { return Ok(false); }
+                // if it's an `AboutMe` message
+                // insert the new name, keeping the old one. someone we've never had a name for isn't renaming,
+                // they're just introducing themselves
+                let old_name = names.insert(from, name.clone());
+                if JSON_OUTPUT.load(Ordering::Relaxed) {
+                    JsonEvent::emit("name", from, name.clone(), None);
+                } else if let Some(old_name) = &old_name {
+                    println!("{}", format!("> {} is now known as {}", old_name, name).blue());
+                } else {
+                    println!("{}", format!("> {} joined as {}", from.fmt_short(), name).blue());
+                }
+                match old_name {
+                    Some(old_name) => transcribe(shared, &format!("> {old_name} is now known as {name}")),
+                    None => transcribe(shared, &format!("> {} joined as {name}", from.fmt_short())),
+                }
+            }
+            ChatMessage::Message { from, text, sent_at } => {
+                // if it's a `Message` message, get the name from the map and print the message
+                let name = get_name(&names, from);
+                // a stray escape code or newline could recolor the terminal or fake a "> " line from us
+                let text = sanitize(text.trim());
+                // a sender whose clock is off shouldn't get to post from the future, or from 1970
+                let sent_at = believable_sent_at(sent_at, unix_millis());
+                // so someone coming back to a busy room can see where they left off
+                let last_active = *shared.last_active.lock().expect("should be able to acquire lock");
+                if last_active.elapsed() >= AWAY_AFTER && *marked_away != Some(last_active) {
+                    *marked_away = Some(last_active);
+                    if !QUIET.load(Ordering::Relaxed) {
+                        status("── new messages ──".dark_grey());
+                    }
+                }
+                if JSON_OUTPUT.load(Ordering::Relaxed) {
+                    JsonEvent::emit("message", from, name.clone(), Some(text.clone()));
+                } else {
+                    println!("{}{}: {}", timestamp(sent_at).dark_grey(), name.as_str().bold().magenta(), style_chat_text(&text));
+                }
+                transcribe(shared, &format!("{name}: {text}"));
+                // our own messages never come back to us, so anything here is from someone else
+                if BELL.load(Ordering::Relaxed) && last_bell.is_none_or(|rung| rung.elapsed() >= BELL_COOLDOWN) {
+                    print!("\x07");
+                    stdout().flush()?;
+                    *last_bell = Some(Instant::now());
+                }
+                recent_messages.insert(message.nonce, RecentMessage { author_id: from, author: name.clone(), text: text.clone(), reactions: BTreeMap::new() });
+                recent_order.push_back(message.nonce);
+                if recent_order.len() > shared.history_limit && let Some(oldest) = recent_order.pop_front() {
+                    recent_messages.remove(&oldest);
+                }
+                *shared.last_message.lock().expect("should be able to acquire lock") = Some(message.nonce);
+                remember_for_backlog(shared, BacklogEntry { from, name, text, at: sent_at });
+                *caught_up = true;
+            }
+            ChatMessage::Joined { from } => {
+                if !present.insert(from) { return Ok(false); }
+                let name = get_name(&names, from);
+                if JSON_OUTPUT.load(Ordering::Relaxed) {
+                    JsonEvent::emit("joined", from, name.clone(), None);
+                } else {
+                    println!("{}", format!("> {name} joined").blue());
+                }
+                transcribe(shared, &format!("> {name} joined"));
+            }
+            ChatMessage::Left { from } => {
+                present.remove(&from);
+                // forgotten so /list doesn't keep them, and coming back says "joined as" again
+                let name = names.remove(&from).unwrap_or_else(|| from.fmt_short().to_string());
+                if JSON_OUTPUT.load(Ordering::Relaxed) {
+                    JsonEvent::emit("left", from, name.clone(), None);
+                } else {
+                    println!("{}", format!("> {name} left").blue());
+                }
+                transcribe(shared, &format!("> {name} left"));
+            }
+            ChatMessage::Action { from, text } => {
+                let name = get_name(&names, from);
+                let text = sanitize(text.trim());
+                if JSON_OUTPUT.load(Ordering::Relaxed) {
+                    JsonEvent::emit("action", from, name.clone(), Some(text.clone()));
+                } else {
+                    println!("{}", format!("* {name} {text}").dark_grey().italic());
+                }
+                transcribe(shared, &format!("* {name} {text}"));
+                *caught_up = true;
+            }
+            ChatMessage::HostAddr { from, addr } => {
+                if from != shared.moderator || addr.node_id != from { return Ok(false); }
+                let Some(discovery) = &shared.discovery else { return Ok(false) };
+                if let Some(relay_url) = &addr.relay_url {
+                    status(format!("> the host moved to relay {relay_url}").blue().dim());
+                }
+                discovery.add_node_info(addr);
+            }
+            ChatMessage::GameCancel { from } => {
+                let mut requester = shared.game_request_tracker.lock().expect("should be able to acquire lock");
+                // only the one waiting can take their request back
+                if requester.as_ref().is_none_or(|(requester, _)| *requester != from) { return Ok(false); }
+                *requester = None;
+                let name = get_name(&names, from);
+                if JSON_OUTPUT.load(Ordering::Relaxed) {
+                    JsonEvent::emit("game_cancel", from, name, None);
+                } else {
+                    println!("{}", format!("> {} left the minimal queue", name).blue());
+                }
+            }
+            ChatMessage::GameRequest { from, config } => {
+                // lock will be released at end of scope
+                let mut requester = shared.game_request_tracker.lock().expect("should be able to acquire lock");
+                // the host passes the queue along to every joiner, everyone else already knew
+                if requester.as_ref() == Some(&(from, config.clone())) { return Ok(false); }
+                *requester = Some((from, config));
+                let name = get_name(&names, from);
+                if JSON_OUTPUT.load(Ordering::Relaxed) {
+                    JsonEvent::emit("game_request", from, name, None);
+                } else {
+                    println!("{}", format!("> {} is in the minimal queue, use {}min to join!", name, shared.command_prefix).blue());
+                }
+            } // released here
+            ChatMessage::GameStart { from, orig_sender, game_id, config } => {
+                // lock will be released at end of scope
+                // it gets repeated until the other player shows up for it, only the first one is news
+                if shared.active_games.lock().expect("should be able to acquire lock").get(&short_game_id(game_id)).is_some_and(|game| game.game_id == game_id) {
+                    return Ok(false);
+                }
+                let mut requester = shared.game_request_tracker.lock().expect("should be able to acquire lock");
+                *requester = None; // the queue is now empty since a game has started
+                // the reason for including orig_sender is because we might have joined the chat
+                // after the request was sent. it also tells /games who is playing
+                let accepter_name = get_name(&names, from);
+                let sender_name = get_name(&names, orig_sender);
+                let mut active_games = shared.active_games.lock().expect("should be able to acquire lock");
+                active_games.retain(|_, game| game.started.elapsed() < ACTIVE_GAME_EXPIRY);
+                active_games.insert(short_game_id(game_id), ActiveGame {
+                    game_id,
+                    player_ids: [orig_sender, from],
+                    players: [sender_name.clone(), accepter_name.clone()],
+                    started: Instant::now(),
+                });
+                if JSON_OUTPUT.load(Ordering::Relaxed) {
+                    JsonEvent::emit("game_start", from, accepter_name, Some(sender_name));
+                } else {
+                    println!("{}", format!("> {} started a game with {}!", accepter_name, sender_name).blue());
+                }
+                if orig_sender == shared.our_id && !config.fits_layout() {
+                    // we'd never have asked for this, and a vbox that big would draw over the rest of the board
+                    status("> your invite was accepted with settings that don't fit on screen, not starting it".yellow());
+                } else if orig_sender == shared.our_id {
+                    status("> your invite was accepted, starting a game!".green());
+                    tokio::spawn(begin_game(game_id, Opponent::Player { id: from, bootstrap: vec![from], joined: None }, config, shared.clone()));
+                } // released here
+            }
+            ChatMessage::Kick { from, target } => {
+                // `from` was checked against whoever signed the message, so this is the host's key talking
+                if from != shared.moderator { return Ok(false); }
+                let name = get_name(&names, target);
+                if target == shared.our_id {
+                    status("> you were kicked from the room by the host, leaving".red());
+                    kicked_out = true;
+                } else {
+                    kicked.insert(target);
+                    if JSON_OUTPUT.load(Ordering::Relaxed) {
+                        JsonEvent::emit("kick", target, name, None);
+                    } else {
+                        println!("{}", format!("> {name} was kicked by the host").yellow());
+                    }
+                }
+            }
+            ChatMessage::Backlog { from, messages } => {
+                if *caught_up || from != shared.moderator { return Ok(false); }
+                *caught_up = true;
+                // the host is only trusted so far, everything in here gets the same cleaning as live chat
+                for entry in messages.into_iter().take(MAX_BACKLOG_MESSAGES) {
+                    let name = clean_nickname(&entry.name);
+                    let text: String = sanitize(entry.text.trim()).chars().take(MAX_BACKLOG_TEXT_CHARS).collect();
+                    if JSON_OUTPUT.load(Ordering::Relaxed) {
+                        JsonEvent::emit("backlog", entry.from, name, Some(text));
+                    } else {
+                        println!("{}", format!("  (earlier) {}{name}: {text}", timestamp(entry.at)).dark_grey());
+                    }
+                }
+            }
+            ChatMessage::RoomTopic { from, text } => {
+                if from != shared.moderator { return Ok(false); }
+                let text = clean_topic_line(&text);
+                let mut topic_line = shared.topic_line.lock().expect("should be able to acquire lock");
+                // the host sends it again for every joiner, it's only news the first time
+                if topic_line.as_ref() == Some(&text) { return Ok(false); }
+                *topic_line = Some(text.clone());
+                if JSON_OUTPUT.load(Ordering::Relaxed) {
+                    JsonEvent::emit("topic", from, get_name(&names, from), Some(text));
+                } else {
+                    println!("{}", format!("> room topic: {text}").blue());
+                }
+            }
+            ChatMessage::Attachment { from, name: file_name, hash } => {
+                let name = get_name(&names, from);
+                let file_name = clean_attachment_name(&file_name);
+                if JSON_OUTPUT.load(Ordering::Relaxed) {
+                    JsonEvent::emit("attachment", from, name, Some(format!("{file_name} {}", data_encoding::HEXLOWER.encode(&hash))));
+                } else {
+                    println!("{}", format!("> {name} shared {file_name} ({})", short_hash(&hash)).blue());
+                }
+            }
+            ChatMessage::GameOver { from: _, game_id } => {
+                shared.active_games.lock().expect("should be able to acquire lock").remove(&short_game_id(game_id));
+            }
+            ChatMessage::Edit { from, target_nonce, new_text } => {
+                // lines already printed can't be changed, so the new version is shown under them.
+                // edits to messages we never saw, or that aren't the sender's own, are dropped
+                let Some(recent) = recent_messages.get_mut(&target_nonce) else { return Ok(false) };
+                if recent.author_id != from { return Ok(false); }
+                recent.text = sanitize(new_text.trim());
+                if JSON_OUTPUT.load(Ordering::Relaxed) {
+                    JsonEvent::emit("edit", from, recent.author.clone(), Some(recent.text.clone()));
+                } else {
+                    println!("{}: {} {}", recent.author.as_str().bold().magenta(), recent.text.as_str().cyan(), "(edited)".dark_grey());
+                }
+            }
+            ChatMessage::Delete { from, target_nonce } => {
+                if recent_messages.get(&target_nonce).is_none_or(|recent| recent.author_id != from) { return Ok(false); }
+                let Some(recent) = recent_messages.remove(&target_nonce) else { return Ok(false) };
+                recent_order.retain(|nonce| *nonce != target_nonce);
+                if JSON_OUTPUT.load(Ordering::Relaxed) {
+                    JsonEvent::emit("delete", from, recent.author, None);
+                } else {
+                    println!("{}", format!("  ✗ {} deleted: {}", recent.author, recent.text.as_str().crossed_out()).dark_grey());
+                }
+            }
+            ChatMessage::Reaction { from, target_nonce, emoji } => {
+                if !REACTIONS.contains(&emoji.as_str()) { return Ok(false); }
+                let name = get_name(&names, from);
+                if JSON_OUTPUT.load(Ordering::Relaxed) {
+                    JsonEvent::emit("reaction", from, name, Some(emoji));
+                    return Ok(false);
+                }
+                match recent_messages.get_mut(&target_nonce) {
+                    Some(recent) => {
+                        *recent.reactions.entry(emoji).or_insert(0) += 1;
+                        let counts: Vec<_> = recent.reactions.iter().map(|(emoji, count)| format!("{emoji} {count}")).collect();
+                        let snippet: String = recent.text.chars().take(24).collect();
+                        let ellipsis = if recent.text.chars().count() > 24 { "…" } else { "" };
+                        println!("{}", format!("  ↳ {name} reacted to {}: \"{snippet}{ellipsis}\"  [{}]", recent.author, counts.join("  ")).dark_grey());
+                    }
+                    // probably one of our own messages, one from before we joined, or one old enough
+                    // to have fallen out of the history
+                    None => println!("{}", format!("  ↳ {name} reacted {emoji}").dark_grey()),
+                }
+            }
+        }
+    }
+    Ok(kicked_out)
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-enum GameMessage {
-    Aborted {}
-}
+// how often the host looks at whether its relay changed, when nothing else wakes the subscribe loop
+const RELAY_CHECK_INTERVAL: Duration = Duration::from_secs(30);
 
-impl MinimalMessage {
-    fn from_bytes(bytes: &[u8]) -> Result<Self> {
-        serde_json::from_slice(bytes).map_err(Into::into)
-    }
-    pub fn new(body: MinimalMessageType) -> Self {
-        Self { body, nonce: rand::random(), }
+// joiners reach the host through the relay it had when they joined. if the host gets moved to another
+// one they'd lose it, so it tells the room where it is now and joiners update their discovery entry
+async fn announce_host_relay(shared: &SharedState, last_relay: &mut Option<RelayUrl>) -> Result<()> {
+    let Some(endpoint) = &shared.endpoint else { return Ok(()) };
+    let addr = endpoint.node_addr();
+    if addr.relay_url.is_none() || addr.relay_url == *last_relay {
+        return Ok(());
     }
-    pub fn to_vec(&self) -> Vec<u8> {
-        serde_json::to_vec(self).expect("serde_json::to_vec is infallible")
-    }
-}
-
-fn get_name(names: &HashMap<PublicKey, String>, from: PublicKey) -> String {
-    names
-        .get(&from)
-        .map_or_else(|| from.fmt_short().to_string(), String::to_string)
+    last_relay.clone_from(&addr.relay_url);
+    let message = MinimalMessage::new(MinimalMessageType::Chat(ChatMessage::HostAddr { from: shared.our_id, addr }));
+    shared.chat_sender.broadcast(&message).await
 }
 
 // Handle incoming events
-async fn subscribe_loop(mut receiver: GossipReceiver, our_id: PublicKey, gossip: Arc<Gossip>, game_request_tracker: Arc<Mutex<Option<PublicKey>>>) -> Result<()> {
-    // keep track of the mapping between `NodeId`s and names
-    let mut names = HashMap::new();
-    // iterate over all events
-    while let Some(event) = receiver.try_next().await? {
+async fn subscribe_loop(mut receiver: GossipReceiver, shared: SharedState, neighbor_count: tokio::sync::watch::Sender<usize>) -> Result<()> {
+    let mut state = ReceiveState::default();
+    let mut host_relay = shared.endpoint.as_ref().and_then(|endpoint| endpoint.node_addr().relay_url);
+    // everyone `--allow` has already had a kick sent for, so they only get the one
+    let mut turned_away = HashSet::new();
+    // peers we've already said we can't read something from, so a newer client doesn't fill the screen
+    let mut unreadable_from = HashSet::new();
+    // same for signers caught putting someone else's id on their messages
+    let mut spoofing = HashSet::new();
+    // this is a new subscription, whoever the last one was connected to doesn't matter anymore
+    *shared.neighbors.lock().expect("should be able to acquire lock") = receiver.neighbors().collect();
+    // iterate over all events, waking up every so often even without any so the host can check its relay
+    loop {
+        let next = tokio::time::timeout(RELAY_CHECK_INTERVAL, receiver.try_next()).await;
+        if shared.our_id == shared.moderator {
+            announce_host_relay(&shared, &mut host_relay).await?;
+        }
+        let Ok(next) = next else { continue };
+        let Some(event) = next? else { break };
+        match event {
+            Event::NeighborUp(id) => {
+                neighbor_count.send_modify(|count| *count += 1);
+                show_neighbor_count(*neighbor_count.borrow());
+                log_event(format!("neighbor up {} ({} connected)", id.fmt_short(), *neighbor_count.borrow()));
+                shared.neighbors.lock().expect("should be able to acquire lock").insert(id);
+                if shared.turns_away(id) && turned_away.insert(id) {
+                    turn_away(&shared, id).await?;
+                }
+                // only the host replays, otherwise a joiner would get one of everything from every neighbor
+                if shared.our_id == shared.moderator {
+                    send_topic_line(&shared).await?;
+                    send_game_request(&shared).await?;
+                    send_backlog(&shared).await?;
+                }
+            }
+            Event::NeighborDown(id) => {
+                neighbor_count.send_modify(|count| *count = count.saturating_sub(1));
+                show_neighbor_count(*neighbor_count.borrow());
+                log_event(format!("neighbor down {} ({} connected)", id.fmt_short(), *neighbor_count.borrow()));
+                shared.neighbors.lock().expect("should be able to acquire lock").remove(&id);
+            }
+            _ => {}
+        }
         // if the Event is a `GossipEvent::Received`, let's deserialize the message:
         if let Event::Received(msg) = event {
-            // deserialize the message and match on the message type:
-            if let MinimalMessageType::Chat(chat_message) = MinimalMessage::from_bytes(&msg.content)?.body {
-                match chat_message {
-                    ChatMessage::AboutMe { from, name } => {
-                        // if it's an `AboutMe` message
-                        // check for the old name first
-                        let old_name = get_name(&names, from);
-                        // insert the new name
-                        names.insert(from, name.clone());
-                        println!("{}", format!("> {} is now known as {}", old_name, name).blue());
-                    }
-                    ChatMessage::Message { from, text } => {
-                        // if it's a `Message` message, get the name from the map and print the message
-                        let name = get_name(&names, from);
-                        println!("{}: {}", name.bold().magenta(), text.trim().cyan());
-                    }
-                    ChatMessage::GameRequest { from } => {
-                        // lock will be released at end of scope
-                        let mut requester = game_request_tracker.lock().expect("should be able to acquire lock");
-                        *requester = Some(from);
-                        let name = get_name(&names, from);
-                        println!("{}", format!("> {} is in the minimal queue, use /min to join!", name).blue());
-                    } // released here
-                    ChatMessage::GameStart { from, orig_sender, game_id } => {
-                        // lock will be released at end of scope
-                        let mut requester = game_request_tracker.lock().expect("should be able to acquire lock");
-                        *requester = None; // the queue is now empty since a game has started
-                        // the reason for including orig_sender is because we might have joined the chat
-                        // after the request was sent. currently we don't need to know who is currently
-                        // in a game but it could be useful later
-                        let accepter_name = get_name(&names, from);
-                        let sender_name = get_name(&names, orig_sender);
-                        println!("{}", format!("> {} started a game with {}!", accepter_name, sender_name).blue());
-                        if orig_sender == our_id {
-                            println!("{}", "> your invite was accepted, starting a game!".green());
-                            tokio::spawn(begin_game(game_id, gossip.clone(), vec![from]));
-                        } // released here
+            dump_raw("chat", msg.delivered_from, &msg.content);
+            // gossip has its own limit, but a peer with a bigger `--gossip-max-message-size` can still get past it
+            if msg.content.len() > message_limit() {
+                log_event(format!("dropped a {} byte message via {}", msg.content.len(), msg.delivered_from.fmt_short()));
+                continue;
+            }
+            // deserialize the message and match on the message type. something we can't read is most likely
+            // from a newer version, that's no reason to stop listening to everything else
+            let (signer, message) = match MinimalMessage::from_signed(&msg.content) {
+                Ok(signed) => signed,
+                Err(e) => {
+                    if unreadable_from.insert(msg.delivered_from) {
+                        status(format!("> skipping messages via {} that this version can't read or check ({e:#})", msg.delivered_from.fmt_short()).yellow());
                     }
+                    continue;
                 }
+            };
+            if !message.sent_by(signer) {
+                if spoofing.insert(signer) {
+                    status(format!("> ignoring {}, who is sending messages under somebody else's id", signer.fmt_short()).red());
+                    log_event(format!("spoofed message from {}", signer.fmt_short()));
+                }
+                continue;
+            }
+            // someone not on the list that got in through another peer. whatever they said is dropped either way
+            if let MinimalMessageType::Chat(chat_message) = &message.body && shared.turns_away(chat_message.sender()) {
+                if turned_away.insert(chat_message.sender()) {
+                    turn_away(&shared, chat_message.sender()).await?;
+                }
+                continue;
+            }
+            if !state.first_sighting(message.nonce) {
+                continue;
+            }
+            let free = matches!(message.body, MinimalMessageType::Chat(ChatMessage::AboutMe { .. }));
+            if !state.allow_from(signer, free) {
+                continue;
+            }
+            // handled without awaiting, so none of its locks are held while waiting on anything
+            let kicked_out = handle_message(message, &shared, &mut state)?;
+            if kicked_out {
+                // leave the same way /quit does. if the input loop is already gone there's nobody to tell
+                let _ = shared.commands.send(format!("{}quit", shared.command_prefix)).await;
+                return Ok(());
             }
         }
     }
-    println!("{}", "> chat manager thread was closed.".red());
-    Ok(())
+    bail!("the room's gossip stream closed")
+}
+
+// the subscribe loop only stops on its own when we're kicked. anything else, an error or even a panic,
+// would leave everything typed going nowhere without a word, so say so and try to get back in
+async fn watch_subscription(receiver: GossipReceiver, shared: SharedState, neighbor_count: tokio::sync::watch::Sender<usize>) {
+    let commands = shared.commands.clone();
+    let prefix = shared.command_prefix.clone();
+    match AssertUnwindSafe(subscribe_loop(receiver, shared, neighbor_count)).catch_unwind().await {
+        Ok(Ok(())) => return,
+        Ok(Err(e)) => {
+            status(format!("> stopped hearing from the room ({e:#}), rejoining...").red());
+            log_event(format!("subscription ended: {e:#}"));
+        }
+        Err(_) => {
+            status("> the chat receiver crashed, rejoining...".red());
+            log_event("subscription crashed");
+        }
+    }
+    // goes through the input loop like a typed command, since that's what owns the sender and subscription
+    let _ = commands.send(format!("{prefix}rejoin")).await;
+}
+
+// windows consoles end lines with \r\n, and a \r left behind ends up inside nicknames and command
+// arguments, which only get split on spaces. lines go on without their ending at all, the same as the
+// ones background tasks send through `commands`
+fn strip_line_ending(line: &str) -> &str {
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    line.strip_suffix('\r').unwrap_or(line)
 }
 
 fn input_loop(line_tx: tokio::sync::mpsc::Sender<String>) -> Result<()> {
@@ -342,7 +3180,7 @@ fn input_loop(line_tx: tokio::sync::mpsc::Sender<String>) -> Result<()> {
     let stdin = std::io::stdin(); // We get `Stdin` here.
     loop {
         stdin.read_line(&mut buffer)?;
-        line_tx.blocking_send(buffer.clone())?;
+        line_tx.blocking_send(strip_line_ending(&buffer).to_string())?;
         buffer.clear();
     }
 }
@@ -351,97 +3189,1230 @@ fn input_loop(line_tx: tokio::sync::mpsc::Sender<String>) -> Result<()> {
 const MIN_TERM_COLS: u16 = 60;
 const MIN_TERM_ROWS: u16 = 7;
 
-async fn begin_game(game_id: f64, gossip: Arc<Gossip>, bootstrap: Vec<PublicKey>) -> Result<()> {
+// the frame a game gets drawn in, all of the terminal unless the config says to keep it smaller
+fn play_area(term_cols: u16, term_rows: u16) -> min::Frame {
+    let limit = |max: &AtomicU16| Some(max.load(Ordering::Relaxed)).filter(|max| *max > 0);
+    min::Frame::centered(term_cols, term_rows, limit(&MAX_PLAY_COLS), limit(&MAX_PLAY_ROWS))
+}
+// terminals can report tiny or even zero sizes partway through a window drag, so a game is only
+// aborted once the terminal has stayed too small for this long
+const RESIZE_SETTLE: Duration = Duration::from_millis(750);
+
+// whether we can actually play here: a game needs a screen to draw on and keys to read
+fn is_interactive() -> bool {
+    stdin().is_terminal() && stdout().is_terminal()
+}
+
+// mouse capture and the alternate screen are escape sequences that a dumb terminal just prints,
+// so those get a keyboard-only game on the normal screen instead
+fn terminal_is_fancy() -> bool {
+    std::env::var("TERM").map_or(cfg!(windows), |term| !term.is_empty() && term != "dumb")
+}
+
+// set while a game has the terminal in raw mode (and on the alternate screen, if it's fancy), so
+// whatever ends things early knows there's something to undo
+static GAME_SCREEN: AtomicBool = AtomicBool::new(false);
+
+// undo what starting a game did to the terminal
+fn leave_game_screen(fancy: bool) -> Result<()> {
+    disable_raw_mode()?;
+    if fancy {
+        execute!(stdout(), DisableMouseCapture, LeaveAlternateScreen)?;
+    }
+    GAME_SCREEN.store(false, Ordering::Relaxed);
+    Ok(())
+}
+
+// puts the terminal back when it goes out of scope, see `play_game`
+struct TerminalGuard;
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore_terminal();
+    }
+}
+
+// the same, for when we're on our way out and can't do anything about an error anyway. it only does
+// anything if a game still has the terminal, so it's fine to call whenever
+fn restore_terminal() {
+    if GAME_SCREEN.swap(false, Ordering::Relaxed) {
+        let _ = disable_raw_mode();
+        if terminal_is_fancy() {
+            let _ = execute!(stdout(), DisableMouseCapture, LeaveAlternateScreen);
+        }
+    }
+}
+
+fn game_topic(game_id: f64) -> TopicId {
     let mut result = [0u8; 32]; // Initialize with zeros
     let bytes = game_id.to_le_bytes();
     let len = bytes.len();
     result[..len].copy_from_slice(&bytes);
-    let topic = TopicId::from_bytes(result);
-    println!("{}", "> waiting for other player...".blue().dim());
-    let (sender, receiver) = gossip.subscribe_and_join(topic, bootstrap).await?.split();
-    // open yet another thread to deal with the sub events
-    tokio::spawn(game_subscribe_loop(receiver));
+    TopicId::from_bytes(result)
+}
+
+// who is on the other side of a game
+#[derive(Debug, Clone)]
+enum Opponent {
+    // someone else in the room, reached through the game topic. `id` is the only one whose game messages
+    // count. `joined` hears about it once they're there, for whoever is still announcing the game
+    // (see `announce_game_start`)
+    Player { id: NodeId, bootstrap: Vec<PublicKey>, joined: Option<Arc<tokio::sync::Notify>> },
+    // `/min solo`, a bot playing its own board locally
+    Bot,
+    // an offline practice game, nobody at all
+    Nobody,
+}
+
+async fn begin_game(game_id: f64, opponent: Opponent, config: GameConfig, shared: SharedState) -> Result<GameOutcome> {
+    let announced = matches!(opponent, Opponent::Player { .. });
+    let outcome = play_game(game_id, opponent, config, shared.our_id, shared.gossip.clone().zip(shared.endpoint.clone())).await;
+    // whichever way the game ended, errors included, the chat needs its normal terminal back
+    restore_terminal();
+    if is_interactive() {
+        status("> back in chat.".blue().dim());
+    }
+    // only games with another player were ever announced to the room, or count for /stats
+    if !announced {
+        return outcome;
+    }
+    if let Ok(outcome) = outcome && let Err(e) = Stats::record(&shared.stats_path, shared.our_id, outcome) {
+        status(format!("> couldn't save the result to {}: {e}", shared.stats_path.display()).yellow());
+    }
+    // however the game went, tell the room it's over so it drops off everyone's /games list
+    let message = MinimalMessage::new(MinimalMessageType::Chat(ChatMessage::GameOver { from: shared.our_id, game_id }));
+    shared.chat_sender.broadcast(&message).await?;
+    outcome
+}
+
+// nothing about a game should keep running once we're back in chat, it would only fight the chat for
+// the terminal. queued moves still go out, `send_moves` ends by itself once they have
+fn stop_background(background: Vec<tokio::task::JoinHandle<Result<()>>>) {
+    for task in background {
+        task.abort();
+    }
+}
+
+async fn play_game(game_id: f64, opponent: Opponent, config: GameConfig, our_id: NodeId, network: Option<(Arc<Gossip>, Endpoint)>) -> Result<GameOutcome> {
+    let outgoing = Arc::new(Mutex::new(Outgoing::default()));
+    // nobody sends us anything in a bot or practice game, so there it doesn't matter whose view it is
+    let opponent_view = Arc::new(OpponentView::new(match &opponent { Opponent::Player { id, .. } => *id, _ => our_id }));
+    let (sender, background, moves, mut from_opponent) = match (opponent.clone(), network) {
+        (Opponent::Player { bootstrap, joined, .. }, Some((gossip, endpoint))) => {
+            let topic = game_topic(game_id);
+            status("> waiting for other player...".blue().dim());
+            let Ok(subscribed) = tokio::time::timeout(GAME_JOIN_TIMEOUT, gossip.subscribe_and_join(topic, bootstrap)).await else {
+                status("> game aborted, your opponent never showed up.".yellow());
+                return Ok(GameOutcome::Aborted);
+            };
+            let (sender, receiver) = subscribed?.split();
+            if let Some(joined) = joined {
+                joined.notify_one();
+            }
+            let sender = Outbox::gossip(sender, &endpoint);
+            let (to_game, from_opponent) = tokio::sync::mpsc::channel(16);
+            // open yet another thread to deal with the sub events
+            let listener = tokio::spawn(game_subscribe_loop(receiver, sender.clone(), game_id, outgoing.clone(), to_game, opponent_view.clone(), terminal_is_fancy()));
+            let resender = tokio::spawn(resend_unacked(sender.clone(), outgoing.clone()));
+            let (moves, to_send) = tokio::sync::mpsc::channel(64);
+            tokio::spawn(send_moves(game_id, sender.clone(), outgoing.clone(), to_send));
+            (sender, vec![listener, resender], Some(moves), Some(from_opponent))
+        }
+        // a bot or practice game, there's nobody on the network to wait for or tell anything
+        _ => (Outbox::Offline, vec![], None, None),
+    };
+    // piped or redirected, there is no screen to draw on or keys to read
+    if !is_interactive() {
+        let message = MinimalMessage::new(MinimalMessageType::Game(GameMessage::Aborted {}));
+        sender.broadcast(&message).await?;
+        status("> game aborted, playing needs minimal to be run in an interactive terminal.".red());
+        stop_background(background);
+        return Ok(GameOutcome::Aborted);
+    }
+    // nobody to agree with in a bot or practice game, the game id will do
+    let seed = match &mut from_opponent {
+        Some(from_opponent) => {
+            status("> agreeing on a board with your opponent...".blue().dim());
+            match agree_on_seed(game_id, &sender, from_opponent).await {
+                Ok(seed) => seed,
+                Err(e) => {
+                    let message = MinimalMessage::new(MinimalMessageType::Game(GameMessage::Aborted {}));
+                    sender.broadcast(&message).await?;
+                    status(format!("> game aborted, {e}.").yellow());
+                    stop_background(background);
+                    return Ok(GameOutcome::Aborted);
+                }
+            }
+        }
+        None => game_id.to_bits(),
+    };
+    let fancy = terminal_is_fancy();
+    if !fancy {
+        status("> this terminal doesn't look like it handles the mouse, use the arrow keys and space instead".yellow());
+    }
+    status("> press ? during the game to see the controls".blue().dim());
     let (mut term_cols, mut term_rows) = size()?;
     // set up terminal stuff
     let mut event_reader = EventStream::new();
     let mut stdout = stdout();
     stdout.flush()?;
     enable_raw_mode()?;
-    execute!(stdout, EnableMouseCapture, EnterAlternateScreen)?;
+    GAME_SCREEN.store(true, Ordering::Relaxed);
+    // from here on every way out of this function, returns, `?` and panics alike, gets the terminal back
+    let _terminal = TerminalGuard;
+    if fancy {
+        execute!(stdout, EnableMouseCapture, EnterAlternateScreen)?;
+    }
     // before doing anything else ensure that the terminal is big enough
     // if not, just immediately abort.
     if (term_cols < MIN_TERM_COLS) || (term_rows < MIN_TERM_ROWS) {
         let message = MinimalMessage::new(MinimalMessageType::Game(GameMessage::Aborted {}));
-        sender.broadcast(message.to_vec().into()).await?;
-        println!("{}", format!("> game aborted due to terminal being too small (should be at least {MIN_TERM_COLS} cols x {MIN_TERM_ROWS} rows).").yellow());
+        sender.broadcast(&message).await?;
+        leave_game_screen(fancy)?;
+        status(format!("> game aborted due to terminal being too small (should be at least {MIN_TERM_COLS} cols x {MIN_TERM_ROWS} rows).").yellow());
+        stop_background(background);
+        return Ok(GameOutcome::Aborted);
+    }
+    let mut game_state = min::MinimalGameState::new(&config, seed);
+    game_state.set_title(match opponent {
+        Opponent::Player { .. } => "minimal",
+        Opponent::Bot => "minimal · solo",
+        Opponent::Nobody => "minimal · practice",
+    });
+    if moves.is_some() {
+        opponent_view.board.lock().expect("should be able to acquire lock").start(min::MinimalGameState::new(&config, seed));
     }
-    let game_state = min::MinimalGameState::new();
+    // the bot gets the same seed, so the same vbox, just like a real opponent would
+    let mut bot = matches!(opponent, Opponent::Bot).then(|| min::MinimalGameState::new(&config, seed));
     let mut cursor_col = 0; let mut cursor_row = 0;
-    while let Some(event) = event_reader.try_next().await? {
+    // there is no way to avoid redrawing the entire screen iirc, so only do it when something actually changed.
+    // start dirty so the first frame shows up without waiting for an event
+    let mut dirty = true;
+    // anything that ends the game without saying otherwise is an abort
+    let mut outcome = GameOutcome::Aborted;
+    // when the terminal last became too small, cleared again once it's big enough
+    let mut too_small_since: Option<Instant> = None;
+    // the controls are up instead of the board, see `min::help_ui`
+    let mut showing_help = false;
+    loop {
+        let frame = play_area(term_cols, term_rows);
+        // the game works in the frame's cells, the cursor and mouse are in the terminal's
+        let slot_at = |game_state: &min::MinimalGameState, col, row| {
+            let (col, row) = frame.local(col, row);
+            game_state.slot_at(col, row)
+        };
+        if dirty && showing_help && term_cols >= MIN_TERM_COLS && term_rows >= MIN_TERM_ROWS {
+            min::help_ui(&mut stdout, frame)?;
+            stdout.flush()?;
+            dirty = false;
+        } else if dirty && term_cols >= MIN_TERM_COLS && term_rows >= MIN_TERM_ROWS {
+            // also it seems like using position() causes the entire terminal to just. crash. so I guess not doing that.
+            // instead, keep track of the mouse position below
+            let (col, row) = frame.local(cursor_col, cursor_row);
+            game_state.ui(&mut stdout, frame, col, row)?;
+            if let Some(board) = &opponent_view.board.lock().expect("should be able to acquire lock").board {
+                board.summary_ui(&mut stdout, frame, "them")?;
+            } else if let Some(bot) = &bot {
+                bot.summary_ui(&mut stdout, frame, "bot")?;
+            }
+            // drawing leaves the terminal cursor wherever the frame ended, put it back under the mouse
+            execute!(stdout, MoveTo(cursor_col, cursor_row))?;
+            stdout.flush()?;
+            dirty = false;
+        }
+        // the opponent's moves only change what's on screen, so they just wake this up for a redraw.
+        // the opponent ending the game wakes it up too, see below
+        let next = async { event_reader.try_next().await.map(Some) }
+            .or(async { opponent_view.changed.notified().await; Ok(None) });
+        let next = match too_small_since {
+            None => next.await?,
+            Some(since) => match tokio::time::timeout(RESIZE_SETTLE.saturating_sub(since.elapsed()), next).await {
+                Ok(next) => next?,
+                Err(_) => {
+                    // it never got big again
+                    leave_game_screen(fancy)?;
+                    let message = MinimalMessage::new(MinimalMessageType::Game(GameMessage::Aborted {}));
+                    sender.broadcast(&message).await?;
+                    status(format!("> game aborted due to terminal being resized to a too small size (should be at least {MIN_TERM_COLS} cols x {MIN_TERM_ROWS} rows).").yellow());
+                    break
+                }
+            },
+        };
         if !is_raw_mode_enabled()? {
             // if raw mode was unexpectedly disabled, the game probably ended
             // todo: make sure that we are on the alternate screen (raw mode is expected to be off on the main one)
             // (doesn't need to be done till I implement switching screens in-game)
-            println!("{}", "> aborting because opponent quit.".yellow());
+            // the subscribe loop already said why, and left how it ended in `ended`. nothing there is an abort
+            if let Some(ended) = *opponent_view.ended.lock().expect("should be able to acquire lock") {
+                outcome = ended;
+            }
             break
         }
-        // re-rendering time!! there is no way to avoid redrawing the entire screen iirc, so just do it
-        // also it seems like using position() causes the entire terminal to just. crash. so I guess not doing that.
-        // instead, keep track of the mouse position above
-        game_state.ui(term_cols, term_rows, cursor_col, cursor_row)?;
+        let Some(event) = next else {
+            dirty = true;
+            continue
+        };
+        let Some(event) = event else { break };
+        // what this event did to our board, if anything, whether that was by mouse or keyboard
+        let mut played = None;
         match event {
+            Key(key_event) if matches!(key_event.code, KeyCode::Char('?') | KeyCode::Char('h')) => {
+                showing_help = !showing_help;
+                dirty = true;
+            }
+            // the board isn't showing, so clicking or moving around on it would be guesswork
+            Key(key_event) if showing_help && !matches!(key_event.code, KeyCode::Char('q') | KeyCode::Char('s')) => {}
+            Mouse(_) if showing_help => {}
             Key(key_event) => {
                 if key_event.code == KeyCode::Char('q') {
                     // quit
-                    disable_raw_mode()?;
-                    execute!(stdout, DisableMouseCapture, LeaveAlternateScreen)?;
+                    leave_game_screen(fancy)?;
                     let message = MinimalMessage::new(MinimalMessageType::Game(GameMessage::Aborted {}));
-                    sender.broadcast(message.to_vec().into()).await?;
-                    println!("{}", "> game aborted.".yellow());
+                    sender.broadcast(&message).await?;
+                    status("> game aborted.".yellow());
+                    break
+                } else if key_event.code == KeyCode::Char('s') {
+                    // give up, which unlike `q` counts as a loss
+                    leave_game_screen(fancy)?;
+                    let message = MinimalMessage::new(MinimalMessageType::Game(GameMessage::Surrender { game_id, from: our_id }));
+                    sender.broadcast(&message).await?;
+                    status("> you surrendered.".yellow());
+                    outcome = GameOutcome::Lost;
                     break
+                } else if key_event.code == KeyCode::Enter {
+                    dirty = game_state.craft_held();
+                    if dirty {
+                        played = Some(min::Move::Craft);
+                    }
+                    // only worth telling anyone about in a game with other people watching
+                    if dirty && moves.is_some() {
+                        let message = MinimalMessage::new(MinimalMessageType::Game(GameMessage::SyncState { game_id, from: our_id, state: game_state.snapshot() }));
+                        sender.broadcast(&message).await?;
+                    }
+                } else if let Some((cols, rows)) = match key_event.code {
+                    // the keyboard can do everything the mouse does, for terminals without mouse support
+                    KeyCode::Left => Some((-1, 0)),
+                    KeyCode::Right => Some((1, 0)),
+                    KeyCode::Up => Some((0, -1)),
+                    KeyCode::Down => Some((0, 1)),
+                    _ => None,
+                } {
+                    let (new_col, new_row) = (cursor_col.saturating_add_signed(cols).min(term_cols.saturating_sub(1)), cursor_row.saturating_add_signed(rows).min(term_rows.saturating_sub(1)));
+                    dirty = slot_at(&game_state, cursor_col, cursor_row) != slot_at(&game_state, new_col, new_row);
+                    (cursor_col, cursor_row) = (new_col, new_row);
+                    execute!(stdout, MoveTo(cursor_col, cursor_row))?;
+                    stdout.flush()?;
+                } else if key_event.code == KeyCode::Char(' ') {
+                    // same as a left click where the cursor is
+                    let (col, row) = frame.local(cursor_col, cursor_row);
+                    played = game_state.click(col, row);
+                    dirty = true;
                 }
             },
             Mouse(mouse_event) => {
+                // mouse events come in terminal cells, `Frame::local` turns them into the frame's (see the layout
+                // constants at the top of min.rs). there's no extra offset for the border
                 match mouse_event.kind {
                     MouseEventKind::Moved => {
+                        // only the hover highlight depends on the mouse, so just moving within a slot needs no redraw
+                        dirty = slot_at(&game_state, cursor_col, cursor_row) != slot_at(&game_state, mouse_event.column, mouse_event.row);
                         cursor_col = mouse_event.column;
                         cursor_row = mouse_event.row;
                         execute!(stdout, MoveTo(cursor_col, cursor_row))?;
                         stdout.flush()?;
                     }
+                    MouseEventKind::Down(MouseButton::Left) => {
+                        let (col, row) = frame.local(mouse_event.column, mouse_event.row);
+                        played = game_state.click(col, row);
+                        dirty = true;
+                    }
                     _ => {}
                 }
             },
             Resize(new_cols, new_rows) => {
-                term_cols = new_cols;
-                term_rows = new_rows;
+                // never let a zero size through, the cursor math below assumes at least one cell
+                term_cols = new_cols.max(1);
+                term_rows = new_rows.max(1);
+                (cursor_col, cursor_row) = (cursor_col.min(term_cols - 1), cursor_row.min(term_rows - 1));
+                dirty = true;
                 if (term_cols < MIN_TERM_COLS) || (term_rows < MIN_TERM_ROWS) {
-                    let message = MinimalMessage::new(MinimalMessageType::Game(GameMessage::Aborted {}));
-                    sender.broadcast(message.to_vec().into()).await?;
-                    println!("{}", format!("> game aborted due to terminal being resized to a too small size (should be at least {MIN_TERM_COLS} cols x {MIN_TERM_ROWS} rows).").yellow());
+                    // give it a moment to settle before giving up on the game
+                    too_small_since.get_or_insert_with(Instant::now);
+                } else {
+                    too_small_since = None;
                 }
             }
             _ => {}
         }
+        if let Some(played) = played {
+            if let Some(moves) = &moves {
+                moves.send(played).await?;
+            }
+            // the bot moves whenever we take something
+            if let min::Move::Take(_) = played && let Some(bot) = &mut bot {
+                min::bot_turn(bot);
+            }
+            // a solo game is over once there's nothing left worth doing on our board. the bot finishes up
+            // whatever it still can, and whoever crafted more wins
+            if let Some(bot) = &mut bot && game_state.is_finished() {
+                while min::bot_turn(bot) {}
+                leave_game_screen(fancy)?;
+                outcome = match game_state.skill_names().len().cmp(&bot.skill_names().len()) {
+                    std::cmp::Ordering::Greater => {
+                        status("> nothing left to craft, you beat the bot!".green());
+                        GameOutcome::Won
+                    }
+                    std::cmp::Ordering::Less => {
+                        status("> nothing left to craft, the bot wins.".yellow());
+                        GameOutcome::Lost
+                    }
+                    std::cmp::Ordering::Equal => {
+                        status("> nothing left to craft, it's a tie.".blue());
+                        GameOutcome::Aborted
+                    }
+                };
+                break
+            }
+        }
     };
+    stop_background(background);
+    if let Some(bot) = bot {
+        status(format!("> you crafted {:?}, the bot crafted {:?}", game_state.skill_names(), bot.skill_names()).blue());
+    }
+    let lost = outgoing.lock().expect("should be able to acquire lock").lost;
+    if lost > 0 {
+        status(format!("> {lost} of your moves were never acknowledged, your opponent may have missed them.").yellow());
+    }
+    Ok(outcome)
+}
+
+// send moves again if the opponent hasn't acked them in time, and give up on them after a few tries
+async fn resend_unacked(sender: Outbox, outgoing: Arc<Mutex<Outgoing>>) -> Result<()> {
+    loop {
+        tokio::time::sleep(ACK_TIMEOUT / 2).await;
+        let mut resend = vec![];
+        {
+            let mut outgoing = outgoing.lock().expect("should be able to acquire lock");
+            let Outgoing { unacked, lost, .. } = &mut *outgoing;
+            unacked.retain(|_, unacked_move| {
+                if unacked_move.sent.elapsed() < ACK_TIMEOUT { return true; }
+                if unacked_move.attempts >= MOVE_ATTEMPTS {
+                    *lost += 1;
+                    return false;
+                }
+                unacked_move.attempts += 1;
+                unacked_move.sent = Instant::now();
+                resend.push(unacked_move.message.clone());
+                true
+            });
+        } // released here, before any awaiting
+        for message in resend {
+            // a fresh nonce each time, gossip would drop an exact repeat as already seen
+            sender.broadcast(&MinimalMessage::new(MinimalMessageType::Game(message))).await?;
+        }
+    }
+}
+
+// watch a game from the chat without taking part: no board, just what the players tell each other
+async fn spectate_game(game_id: f64, players: Vec<PublicKey>, gossip: Arc<Gossip>) -> Result<()> {
+    let short_id = short_game_id(game_id);
+    let (_sender, mut receiver) = gossip.subscribe_and_join(game_topic(game_id), players.clone()).await?.split();
+    while let Some(event) = receiver.try_next().await? {
+        // anything unreadable is skipped, same as in the chat
+        if let Event::Received(msg) = event
+            && let Ok((signer, message)) = MinimalMessage::from_signed(&msg.content)
+            && players.contains(&signer)
+            && message.sent_by(signer)
+            && let MinimalMessageType::Game(game_message) = message.body {
+            match game_message {
+                GameMessage::Aborted {} => {
+                    status(format!("> game {short_id} was aborted, stopped spectating.").yellow());
+                    break
+                }
+                GameMessage::Surrender { from, .. } => {
+                    status(format!("> {} surrendered game {short_id}, stopped spectating.", from.fmt_short()).yellow());
+                    break
+                }
+                GameMessage::SyncState { from, state, .. } => {
+                    let Some(board) = min::MinimalGameState::from_snapshot(state) else { continue };
+                    if let Some(skill) = board.skill_names().last() {
+                        status(format!("> {} crafted {skill} in game {short_id}, and has {:?} so far", from.fmt_short(), board.skill_names()).blue());
+                    }
+                }
+                // moves, acks and the seed handshake are between the players, nothing to show for them yet
+                GameMessage::PlayComponent { .. } | GameMessage::Refund { .. } | GameMessage::Craft { .. } | GameMessage::Ack { .. } | GameMessage::Commit { .. } | GameMessage::Reveal { .. } => {}
+            }
+        }
+    }
     Ok(())
 }
 
-async fn game_subscribe_loop(mut receiver: GossipReceiver) -> Result<()> {
+// `to_game` only gets the seed handshake. nothing reads it once the board is up, so it can fill up,
+// which is why the end of the game goes through `OpponentView::ended` instead
+// `opponent` gets their moves played on it
+async fn game_subscribe_loop(mut receiver: GossipReceiver, sender: Outbox, our_game_id: f64, outgoing: Arc<Mutex<Outgoing>>, to_game: tokio::sync::mpsc::Sender<GameMessage>, opponent: Arc<OpponentView>, fancy: bool) -> Result<()> {
     while let Some(event) = receiver.try_next().await? {
         if let Event::Received(msg) = event {
-            // deserialize the message and match on the message type:
-            if let MinimalMessageType::Game(game_message) = MinimalMessage::from_bytes(&msg.content)?.body {
+            dump_raw("game", msg.delivered_from, &msg.content);
+            // deserialize the message and match on the message type. anything unreadable is skipped without
+            // a word, the board is on screen and there's nowhere to say it
+            let Ok((signer, message)) = MinimalMessage::from_signed(&msg.content) else { continue };
+            // a spectator or anyone else on the topic could otherwise abort the game or steer the seed
+            if signer != opponent.id || !message.sent_by(signer) { continue; }
+            if let MinimalMessageType::Game(game_message) = message.body {
                 match game_message {
                     GameMessage::Aborted {} => {
-                        disable_raw_mode()?;
-                        execute!(stdout(), DisableMouseCapture, LeaveAlternateScreen)?;
-                        println!("{}", "> opponent aborted the game.".yellow());
+                        *opponent.ended.lock().expect("should be able to acquire lock") = Some(GameOutcome::Aborted);
+                        leave_game_screen(fancy)?;
+                        status("> opponent aborted the game.".yellow());
+                        // the game loop is waiting on a key, this gets it back to the chat without one
+                        opponent.changed.notify_one();
+                        break
+                    }
+                    GameMessage::PlayComponent { game_id, seq, .. } | GameMessage::Refund { game_id, seq } | GameMessage::Craft { game_id, seq } if game_id == our_game_id => {
+                        let played = match game_message {
+                            GameMessage::PlayComponent { index, .. } => min::Move::Take(index),
+                            GameMessage::Refund { .. } => min::Move::Refund,
+                            _ => min::Move::Craft,
+                        };
+                        if opponent.board.lock().expect("should be able to acquire lock").receive(seq, played) {
+                            opponent.changed.notify_one();
+                        }
+                        // always ack, even a move we've seen before, since it means our last ack got lost
+                        let message = MinimalMessage::new(MinimalMessageType::Game(GameMessage::Ack { game_id, seq }));
+                        sender.broadcast(&message).await?;
+                    }
+                    GameMessage::Commit { game_id, .. } | GameMessage::Reveal { game_id, .. } if game_id == our_game_id => {
+                        // the seed handshake is done once the board is up, so nobody may be listening anymore.
+                        // the repeats just get dropped then
+                        let _ = to_game.try_send(game_message);
+                    }
+                    GameMessage::Surrender { game_id, .. } if game_id == our_game_id => {
+                        // same as an abort as far as the screen goes, except that we won
+                        *opponent.ended.lock().expect("should be able to acquire lock") = Some(GameOutcome::Won);
+                        leave_game_screen(fancy)?;
+                        status("> your opponent surrendered, you win!".green());
+                        opponent.changed.notify_one();
                         break
                     }
+                    GameMessage::Ack { game_id, seq } if game_id == our_game_id => {
+                        // acks for seqs we aren't waiting on (repeats, or made up) just do nothing
+                        outgoing.lock().expect("should be able to acquire lock").unacked.remove(&seq);
+                    }
+                    _ => {}
                 }
             }
         }
     }
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a file in the temp dir that nothing else (including other test runs) is using
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("minimal-test-{}-{name}", std::process::id()))
+    }
+
+    fn load_config_from(name: &str, contents: &str) -> MinConfig {
+        let path = temp_path(name);
+        fs::write(&path, contents).unwrap();
+        let minconfig = MinConfig::load(path.clone()).unwrap();
+        let _ = fs::remove_file(path);
+        minconfig
+    }
+
+    fn assert_default_config(minconfig: &MinConfig) {
+        let defaults = MinConfig::default();
+        assert_eq!(minconfig.name, defaults.name);
+        assert!(minconfig.bookmarks.is_empty());
+        assert_eq!(minconfig.history_limit, DEFAULT_HISTORY_LIMIT);
+        assert_eq!(minconfig.command_prefix, "/");
+        assert!(minconfig.timestamps);
+        assert_eq!(minconfig.time_format, TimeFormat::TwentyFourHour);
+        assert_eq!(minconfig.secret_key, None);
+    }
+
+    #[test]
+    fn empty_config_loads_defaults() {
+        assert_default_config(&load_config_from("empty.json", ""));
+        assert_default_config(&load_config_from("blank.json", "  \n"));
+    }
+
+    #[test]
+    fn empty_object_config_loads_defaults() {
+        assert_default_config(&load_config_from("object.json", "{}"));
+    }
+
+    #[test]
+    fn missing_config_is_created() {
+        let path = temp_path("missing.json");
+        let _ = fs::remove_file(&path);
+        assert_default_config(&MinConfig::load(path.clone()).unwrap());
+        assert!(path.exists());
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn empty_stats_file_is_no_games() {
+        let path = temp_path("stats.json");
+        fs::write(&path, "\n").unwrap();
+        let stats = Stats::load(&path).unwrap();
+        let _ = fs::remove_file(path);
+        assert!(stats.nodes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn shared_files_hash_like_blake3() {
+        let path = temp_path("share.bin");
+        let bytes: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        fs::write(&path, &bytes).unwrap();
+        let hash = hash_file(path.clone()).await.unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(hash, *blake3::hash(&bytes).as_bytes());
+        assert!(hash_file(path).await.is_err());
+    }
+
+    #[test]
+    fn sent_at_is_only_believed_near_now() {
+        let now = 1_700_000_000_000;
+        assert_eq!(believable_sent_at(now - 5_000, now), now - 5_000);
+        assert_eq!(believable_sent_at(now - MAX_SENT_AT_AGE_MILLIS, now), now - MAX_SENT_AT_AGE_MILLIS);
+        // old clients send 0
+        assert_eq!(believable_sent_at(0, now), now);
+        assert_eq!(believable_sent_at(now - MAX_SENT_AT_AGE_MILLIS - 1, now), now);
+        assert_eq!(believable_sent_at(now + 1, now), now);
+    }
+
+    fn alice() -> NodeId {
+        SecretKey::from_bytes(&[1; 32]).public()
+    }
+
+    fn bob() -> NodeId {
+        SecretKey::from_bytes(&[2; 32]).public()
+    }
+
+    // the json on the wire, field names and all. if one of these changes, older clients on the same
+    // topic can't read it anymore, see the comment on `MinimalMessage`
+    fn assert_wire(body: MinimalMessageType, expected: &str) {
+        assert_eq!(serde_json::to_string(&body).unwrap(), expected);
+        // and it has to come back as the same thing
+        let parsed: MinimalMessageType = serde_json::from_str(expected).unwrap();
+        assert_eq!(serde_json::to_string(&parsed).unwrap(), expected);
+    }
+
+    #[test]
+    fn chat_messages_keep_their_wire_format() {
+        assert_wire(
+            MinimalMessageType::Chat(ChatMessage::AboutMe { from: alice(), name: "alice".to_string() }),
+            r#"{"Chat":{"AboutMe":{"from":"8a88e3dd7409f195fd52db2d3cba5d72ca6709bf1d94121bf3748801b40f6f5c","name":"alice"}}}"#,
+        );
+        assert_wire(
+            MinimalMessageType::Chat(ChatMessage::Message { from: alice(), text: "hi".to_string(), sent_at: 1700000000000 }),
+            r#"{"Chat":{"Message":{"from":"8a88e3dd7409f195fd52db2d3cba5d72ca6709bf1d94121bf3748801b40f6f5c","text":"hi","sent_at":1700000000000}}}"#,
+        );
+        assert_wire(
+            MinimalMessageType::Chat(ChatMessage::GameRequest { from: alice(), config: GameConfig::default() }),
+            r#"{"Chat":{"GameRequest":{"from":"8a88e3dd7409f195fd52db2d3cba5d72ca6709bf1d94121bf3748801b40f6f5c","config":{"starting_bits":40,"colors":6,"skills":3}}}}"#,
+        );
+        assert_wire(
+            MinimalMessageType::Chat(ChatMessage::GameStart { from: bob(), orig_sender: alice(), game_id: 12.5, config: GameConfig::default() }),
+            r#"{"Chat":{"GameStart":{"from":"8139770ea87d175f56a35466c34c7ecccb8d8a91b4ee37a25df60f5b8fc9b394","orig_sender":"8a88e3dd7409f195fd52db2d3cba5d72ca6709bf1d94121bf3748801b40f6f5c","game_id":12.5,"config":{"starting_bits":40,"colors":6,"skills":3}}}}"#,
+        );
+        assert_wire(
+            MinimalMessageType::Chat(ChatMessage::Reaction { from: bob(), target_nonce: [1; 16], emoji: "👍".to_string() }),
+            r#"{"Chat":{"Reaction":{"from":"8139770ea87d175f56a35466c34c7ecccb8d8a91b4ee37a25df60f5b8fc9b394","target_nonce":[1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1],"emoji":"👍"}}}"#,
+        );
+        assert_wire(
+            MinimalMessageType::Chat(ChatMessage::Kick { from: alice(), target: bob() }),
+            r#"{"Chat":{"Kick":{"from":"8a88e3dd7409f195fd52db2d3cba5d72ca6709bf1d94121bf3748801b40f6f5c","target":"8139770ea87d175f56a35466c34c7ecccb8d8a91b4ee37a25df60f5b8fc9b394"}}}"#,
+        );
+        assert_wire(
+            MinimalMessageType::Chat(ChatMessage::Backlog { from: alice(), messages: vec![BacklogEntry { from: bob(), name: "bob".to_string(), text: "hi".to_string(), at: 5 }] }),
+            r#"{"Chat":{"Backlog":{"from":"8a88e3dd7409f195fd52db2d3cba5d72ca6709bf1d94121bf3748801b40f6f5c","messages":[{"from":"8139770ea87d175f56a35466c34c7ecccb8d8a91b4ee37a25df60f5b8fc9b394","name":"bob","text":"hi","at":5}]}}}"#,
+        );
+        assert_wire(
+            MinimalMessageType::Chat(ChatMessage::Edit { from: alice(), target_nonce: [2; 16], new_text: "hey".to_string() }),
+            r#"{"Chat":{"Edit":{"from":"8a88e3dd7409f195fd52db2d3cba5d72ca6709bf1d94121bf3748801b40f6f5c","target_nonce":[2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2],"new_text":"hey"}}}"#,
+        );
+        assert_wire(
+            MinimalMessageType::Chat(ChatMessage::Delete { from: alice(), target_nonce: [3; 16] }),
+            r#"{"Chat":{"Delete":{"from":"8a88e3dd7409f195fd52db2d3cba5d72ca6709bf1d94121bf3748801b40f6f5c","target_nonce":[3,3,3,3,3,3,3,3,3,3,3,3,3,3,3,3]}}}"#,
+        );
+        assert_wire(
+            MinimalMessageType::Chat(ChatMessage::GameOver { from: alice(), game_id: 12.5 }),
+            r#"{"Chat":{"GameOver":{"from":"8a88e3dd7409f195fd52db2d3cba5d72ca6709bf1d94121bf3748801b40f6f5c","game_id":12.5}}}"#,
+        );
+        assert_wire(
+            MinimalMessageType::Chat(ChatMessage::HostAddr { from: alice(), addr: NodeAddr::new(alice()) }),
+            r#"{"Chat":{"HostAddr":{"from":"8a88e3dd7409f195fd52db2d3cba5d72ca6709bf1d94121bf3748801b40f6f5c","addr":{"node_id":"8a88e3dd7409f195fd52db2d3cba5d72ca6709bf1d94121bf3748801b40f6f5c","relay_url":null,"direct_addresses":[]}}}}"#,
+        );
+        assert_wire(
+            MinimalMessageType::Chat(ChatMessage::GameCancel { from: alice() }),
+            r#"{"Chat":{"GameCancel":{"from":"8a88e3dd7409f195fd52db2d3cba5d72ca6709bf1d94121bf3748801b40f6f5c"}}}"#,
+        );
+        assert_wire(
+            MinimalMessageType::Chat(ChatMessage::RoomTopic { from: alice(), text: "games".to_string() }),
+            r#"{"Chat":{"RoomTopic":{"from":"8a88e3dd7409f195fd52db2d3cba5d72ca6709bf1d94121bf3748801b40f6f5c","text":"games"}}}"#,
+        );
+        assert_wire(
+            MinimalMessageType::Chat(ChatMessage::Attachment { from: alice(), name: "a.txt".to_string(), hash: [4; 32] }),
+            r#"{"Chat":{"Attachment":{"from":"8a88e3dd7409f195fd52db2d3cba5d72ca6709bf1d94121bf3748801b40f6f5c","name":"a.txt","hash":[4,4,4,4,4,4,4,4,4,4,4,4,4,4,4,4,4,4,4,4,4,4,4,4,4,4,4,4,4,4,4,4]}}}"#,
+        );
+        assert_wire(
+            MinimalMessageType::Chat(ChatMessage::Action { from: alice(), text: "waves".to_string() }),
+            r#"{"Chat":{"Action":{"from":"8a88e3dd7409f195fd52db2d3cba5d72ca6709bf1d94121bf3748801b40f6f5c","text":"waves"}}}"#,
+        );
+        assert_wire(
+            MinimalMessageType::Chat(ChatMessage::Joined { from: alice() }),
+            r#"{"Chat":{"Joined":{"from":"8a88e3dd7409f195fd52db2d3cba5d72ca6709bf1d94121bf3748801b40f6f5c"}}}"#,
+        );
+        assert_wire(
+            MinimalMessageType::Chat(ChatMessage::Left { from: alice() }),
+            r#"{"Chat":{"Left":{"from":"8a88e3dd7409f195fd52db2d3cba5d72ca6709bf1d94121bf3748801b40f6f5c"}}}"#,
+        );
+    }
+
+    #[test]
+    fn game_messages_keep_their_wire_format() {
+        assert_wire(
+            MinimalMessageType::Game(GameMessage::Aborted {}),
+            r#"{"Game":{"Aborted":{}}}"#,
+        );
+        assert_wire(
+            MinimalMessageType::Game(GameMessage::PlayComponent { game_id: 12.5, seq: 0, index: 3 }),
+            r#"{"Game":{"PlayComponent":{"game_id":12.5,"seq":0,"index":3}}}"#,
+        );
+        assert_wire(
+            MinimalMessageType::Game(GameMessage::Refund { game_id: 12.5, seq: 1 }),
+            r#"{"Game":{"Refund":{"game_id":12.5,"seq":1}}}"#,
+        );
+        assert_wire(
+            MinimalMessageType::Game(GameMessage::Craft { game_id: 12.5, seq: 2 }),
+            r#"{"Game":{"Craft":{"game_id":12.5,"seq":2}}}"#,
+        );
+        assert_wire(
+            MinimalMessageType::Game(GameMessage::Ack { game_id: 12.5, seq: 2 }),
+            r#"{"Game":{"Ack":{"game_id":12.5,"seq":2}}}"#,
+        );
+        assert_wire(
+            MinimalMessageType::Game(GameMessage::Commit { game_id: 12.5, commitment: [5; 32] }),
+            r#"{"Game":{"Commit":{"game_id":12.5,"commitment":[5,5,5,5,5,5,5,5,5,5,5,5,5,5,5,5,5,5,5,5,5,5,5,5,5,5,5,5,5,5,5,5]}}}"#,
+        );
+        assert_wire(
+            MinimalMessageType::Game(GameMessage::Reveal { game_id: 12.5, secret: [6; 32] }),
+            r#"{"Game":{"Reveal":{"game_id":12.5,"secret":[6,6,6,6,6,6,6,6,6,6,6,6,6,6,6,6,6,6,6,6,6,6,6,6,6,6,6,6,6,6,6,6]}}}"#,
+        );
+        assert_wire(
+            MinimalMessageType::Game(GameMessage::Surrender { game_id: 12.5, from: bob() }),
+            r#"{"Game":{"Surrender":{"game_id":12.5,"from":"8139770ea87d175f56a35466c34c7ecccb8d8a91b4ee37a25df60f5b8fc9b394"}}}"#,
+        );
+        assert_wire(
+            MinimalMessageType::Game(GameMessage::SyncState { game_id: 12.5, from: bob(), state: min::MinimalGameState::new(&GameConfig { starting_bits: 3, colors: 1, skills: 1 }, 1).snapshot() }),
+            r#"{"Game":{"SyncState":{"game_id":12.5,"from":"8139770ea87d175f56a35466c34c7ecccb8d8a91b4ee37a25df60f5b8fc9b394","state":{"vbox":["Blue","Stun"],"bits":3,"held":[],"skills":[]}}}}"#,
+        );
+    }
+
+    #[test]
+    fn message_envelope_keeps_its_wire_format() {
+        let message = MinimalMessage { body: MinimalMessageType::Chat(ChatMessage::Joined { from: alice() }), nonce: [7; 16] };
+        assert_eq!(
+            serde_json::to_string(&message).unwrap(),
+            r#"{"body":{"Chat":{"Joined":{"from":"8a88e3dd7409f195fd52db2d3cba5d72ca6709bf1d94121bf3748801b40f6f5c"}}},"nonce":[7,7,7,7,7,7,7,7,7,7,7,7,7,7,7,7]}"#,
+        );
+    }
+
+    // and what it goes out in. ed25519 signatures come out the same every time for the same key and
+    // message, so the whole thing can be pinned down
+    #[test]
+    fn signed_envelope_keeps_its_wire_format() {
+        let message = MinimalMessage { body: MinimalMessageType::Chat(ChatMessage::Joined { from: alice() }), nonce: [7; 16] };
+        let expected = r#"{"from":"8a88e3dd7409f195fd52db2d3cba5d72ca6709bf1d94121bf3748801b40f6f5c","message":"{\"body\":{\"Chat\":{\"Joined\":{\"from\":\"8a88e3dd7409f195fd52db2d3cba5d72ca6709bf1d94121bf3748801b40f6f5c\"}}},\"nonce\":[7,7,7,7,7,7,7,7,7,7,7,7,7,7,7,7]}","signature":"4b7a01fdc2d4731e7de5d00ba1fc73a1defb51c275cee87106cbcd51ac18517f0a5654468fc945c8a06c3244bfebe130233924455507e6baba7fd483a4addb06"}"#;
+        assert_eq!(String::from_utf8(message.to_signed(&SecretKey::from_bytes(&[1; 32]))).unwrap(), expected);
+        let (signer, parsed) = MinimalMessage::from_signed(expected.as_bytes()).unwrap();
+        assert_eq!(signer, alice());
+        assert_eq!(parsed.nonce, [7; 16]);
+    }
+
+    // postcard has no field names to go by, only the order, so its bytes are pinned down too: the
+    // variant indexes, the key as it is, the text with its length in front, `sent_at` as a varint and
+    // the nonce without any length
+    #[test]
+    fn compact_message_keeps_its_wire_format() {
+        let message = MinimalMessage { body: MinimalMessageType::Chat(ChatMessage::Message { from: alice(), text: "hi".to_string(), sent_at: 1700000000000 }), nonce: [7; 16] };
+        let expected = "0001\
+            8a88e3dd7409f195fd52db2d3cba5d72ca6709bf1d94121bf3748801b40f6f5c\
+            026869\
+            80d095ffbc31\
+            07070707070707070707070707070707";
+        assert_eq!(data_encoding::HEXLOWER.encode(&postcard::to_allocvec(&message).unwrap()), expected);
+        let parsed: MinimalMessage = postcard::from_bytes(&data_encoding::HEXLOWER.decode(expected.as_bytes()).unwrap()).unwrap();
+        assert!(matches!(parsed.body, MinimalMessageType::Chat(ChatMessage::Message { text, sent_at: 1700000000000, .. }) if text == "hi"));
+    }
+
+    // what `--compact` buys: a short chat line signed is 432 bytes as json and 157 as postcard. most of
+    // the json is the inner message escaped into a string and the hex signature
+    #[test]
+    fn compact_messages_are_smaller() {
+        let key = SecretKey::from_bytes(&[1; 32]);
+        let message = MinimalMessage { body: MinimalMessageType::Chat(ChatMessage::Message { from: alice(), text: "hi".to_string(), sent_at: 1700000000000 }), nonce: [7; 16] };
+        let signed_as = |message: Vec<u8>, encode: fn(&SignedMessage) -> Vec<u8>| {
+            let signature = key.sign(&message).to_bytes().to_vec();
+            encode(&SignedMessage { from: alice(), message, signature })
+        };
+        let json = serde_json::to_vec(&message).unwrap();
+        let compact = postcard::to_allocvec(&message).unwrap();
+        assert_eq!((json.len(), compact.len()), (183, 59));
+        let json = signed_as(json, |signed| serde_json::to_vec(signed).unwrap());
+        let compact = signed_as(compact, |signed| postcard::to_allocvec(signed).unwrap());
+        assert_eq!((json.len(), compact.len()), (432, 157));
+    }
+
+    #[test]
+    fn combined_seed_is_the_same_both_ways() {
+        let (ours, theirs) = ([3; 32], [0x5a; 32]);
+        assert_eq!(combine_seeds(&ours, &theirs), combine_seeds(&ours, &theirs));
+        assert_eq!(combine_seeds(&ours, &theirs), combine_seeds(&theirs, &ours));
+        // either half changing changes the board
+        assert_ne!(combine_seeds(&ours, &theirs), combine_seeds(&[4; 32], &theirs));
+        assert_ne!(combine_seeds(&ours, &theirs), combine_seeds(&ours, &[0x5b; 32]));
+    }
+
+    #[test]
+    fn doubled_prefix_is_chat() {
+        assert!(matches!(parse_input("/nick foo", "/"), Input::Command(command) if command == "/nick foo"));
+        assert!(matches!(parse_input("//etc/hosts", "/"), Input::Chat(text) if text == "/etc/hosts"));
+        assert!(matches!(parse_input("hello", "/"), Input::Chat(text) if text == "hello"));
+        // commands come out spelled with `/` whatever the prefix is
+        assert!(matches!(parse_input("!nick foo", "!"), Input::Command(command) if command == "/nick foo"));
+        assert!(matches!(parse_input("!!nick foo", "!"), Input::Chat(text) if text == "!nick foo"));
+        assert!(matches!(parse_input("/etc/hosts", "!"), Input::Chat(text) if text == "/etc/hosts"));
+    }
+
+    #[test]
+    fn commands_are_found_by_name() {
+        let mut names = HashSet::new();
+        for command in COMMANDS {
+            assert!(command.name.starts_with('/'));
+            assert!(names.insert(command.name), "{} is in the table twice", command.name);
+            assert_eq!(find_command(command.name).unwrap().help, command.help);
+        }
+        assert_eq!(find_command("/nick").unwrap().usage("!"), "!nick <name>");
+        assert_eq!(find_command("/quit").unwrap().usage("/"), "/quit");
+    }
+
+    // the room's host, who is the moderator in every `shared_state`
+    fn host_key() -> SecretKey {
+        SecretKey::from_bytes(&[9; 32])
+    }
+
+    // what an offline client would have, with nothing from the network and nobody listening for commands
+    fn shared_state(our_id: NodeId) -> SharedState {
+        let (commands, _) = tokio::sync::mpsc::channel(1);
+        SharedState {
+            our_id,
+            moderator: host_key().public(),
+            our_name: Arc::new(Mutex::new(String::new())),
+            stats_path: PathBuf::new(),
+            gossip: None,
+            endpoint: None,
+            discovery: None,
+            chat_sender: Outbox::Offline,
+            commands,
+            command_prefix: "/".to_string(),
+            names: Arc::new(Mutex::new(HashMap::new())),
+            neighbors: Arc::new(Mutex::new(HashSet::new())),
+            game_request_tracker: Arc::new(Mutex::new(None)),
+            last_message: Arc::new(Mutex::new(None)),
+            active_games: Arc::new(Mutex::new(BTreeMap::new())),
+            history_limit: DEFAULT_HISTORY_LIMIT,
+            backlog: Arc::new(Mutex::new(VecDeque::new())),
+            backlog_size: MAX_BACKLOG_MESSAGES,
+            scrollback: Arc::new(Mutex::new(VecDeque::new())),
+            transcript: None,
+            topic_line: Arc::new(Mutex::new(None)),
+            allowlist: Arc::new(HashSet::new()),
+            last_active: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    // what the subscribe loop does with the bytes gossip hands it, minus the rate limit. false if it was
+    // a repeat or got us kicked
+    fn receive(bytes: &[u8], shared: &SharedState, state: &mut ReceiveState) -> bool {
+        let (signer, message) = MinimalMessage::from_signed(bytes).unwrap();
+        assert!(message.sent_by(signer));
+        state.first_sighting(message.nonce) && !handle_message(message, shared, state).unwrap()
+    }
+
+    // a host and one joiner for `room`, the way `--local` brings them up: no relays, no discovery, just
+    // localhost. each test needs its own room, or they'd be after the same port
+    async fn local_room(room: &str) -> (Network, (GossipSender, GossipReceiver), Network, (GossipSender, GossipReceiver)) {
+        let tuning = GossipTuning::default();
+        let host = start_network(host_secret_key(room), &tuning, local_bind(true, true, room)).await.unwrap();
+        let hosted = host_room(room, &host.gossip, 1).await.unwrap();
+        let joiner = start_network(SecretKey::from_bytes(&[1; 32]), &tuning, local_bind(true, false, room)).await.unwrap();
+        let joined = join_room(room, &joiner, None).await.unwrap();
+        (host, hosted, joiner, joined)
+    }
+
+    // the next chat message gossip hands over, skipping neighbors coming and going
+    async fn next_received(receiver: &mut GossipReceiver) -> Vec<u8> {
+        let wait = async {
+            loop {
+                if let Event::Received(msg) = receiver.try_next().await.unwrap().expect("the topic shouldn't close") {
+                    return msg.content.to_vec();
+                }
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(10), wait).await.expect("nothing arrived in time")
+    }
+
+    #[tokio::test]
+    async fn chat_gets_from_one_node_to_another() {
+        // both halves of each subscription are held on to, dropping either one leaves the topic
+        let (host, (_host_sender, mut host_receiver), joiner, (joiner_sender, _joiner_receiver)) = local_room(&format!("test-chat-{}", std::process::id())).await;
+        let outbox = Outbox::gossip(joiner_sender, &joiner.endpoint);
+        let message = MinimalMessage::new(MinimalMessageType::Chat(ChatMessage::Message { from: alice(), text: "hello host".to_string(), sent_at: 0 }));
+        outbox.broadcast(&message).await.unwrap();
+        let bytes = next_received(&mut host_receiver).await;
+        let (signer, received) = MinimalMessage::from_signed(&bytes).unwrap();
+        assert_eq!(signer, alice());
+        assert_eq!(received.nonce, message.nonce);
+        // and from there it's shown like anything else
+        let hosts_client = shared_state(host.endpoint.node_id());
+        assert!(receive(&bytes, &hosts_client, &mut ReceiveState::default()));
+        assert_eq!(hosts_client.scrollback.lock().unwrap()[0].text, "hello host");
+        joiner.router.shutdown().await.unwrap();
+        host.router.shutdown().await.unwrap();
+    }
+
+    // one message through `handle_message`, returning whether it got us kicked
+    fn handle(shared: &SharedState, state: &mut ReceiveState, body: ChatMessage) -> bool {
+        handle_message(MinimalMessage::new(MinimalMessageType::Chat(body)), shared, state).unwrap()
+    }
+
+    // a `Message` from `from`, and the nonce it went out with
+    fn say(shared: &SharedState, state: &mut ReceiveState, from: NodeId, text: &str) -> [u8; 16] {
+        let message = MinimalMessage::new(MinimalMessageType::Chat(ChatMessage::Message { from, text: text.to_string(), sent_at: 0 }));
+        let nonce = message.nonce;
+        assert!(!handle_message(message, shared, state).unwrap());
+        nonce
+    }
+
+    #[test]
+    fn about_me_sets_the_name() {
+        let shared = shared_state(bob());
+        let mut state = ReceiveState::default();
+        handle(&shared, &mut state, ChatMessage::AboutMe { from: alice(), name: "  alice\x1b[31m ".to_string() });
+        assert_eq!(shared.names.lock().unwrap().get(&alice()).map(String::as_str), Some("alice"));
+        // a name that cleans up to nothing is ignored
+        handle(&shared, &mut state, ChatMessage::AboutMe { from: alice(), name: "\n".to_string() });
+        assert_eq!(shared.names.lock().unwrap().get(&alice()).map(String::as_str), Some("alice"));
+    }
+
+    #[test]
+    fn message_is_remembered() {
+        let shared = shared_state(bob());
+        let mut state = ReceiveState::default();
+        let nonce = say(&shared, &mut state, alice(), " hi\x07 ");
+        assert_eq!(*shared.last_message.lock().unwrap(), Some(nonce));
+        assert_eq!(state.recent_messages[&nonce].text, "hi");
+        assert_eq!(shared.scrollback.lock().unwrap().back().unwrap().text, "hi");
+        assert_eq!(shared.backlog.lock().unwrap().len(), 1);
+        assert!(state.caught_up);
+    }
+
+    #[test]
+    fn joined_and_left_track_who_is_here() {
+        let shared = shared_state(bob());
+        let mut state = ReceiveState::default();
+        shared.names.lock().unwrap().insert(alice(), "alice".to_string());
+        handle(&shared, &mut state, ChatMessage::Joined { from: alice() });
+        assert!(state.present.contains(&alice()));
+        handle(&shared, &mut state, ChatMessage::Left { from: alice() });
+        assert!(!state.present.contains(&alice()));
+        assert!(!shared.names.lock().unwrap().contains_key(&alice()));
+    }
+
+    #[test]
+    fn action_counts_as_live_chat() {
+        let shared = shared_state(bob());
+        let mut state = ReceiveState::default();
+        handle(&shared, &mut state, ChatMessage::Action { from: alice(), text: "waves".to_string() });
+        assert!(state.caught_up);
+        // so a backlog coming in after it is too late
+        handle(&shared, &mut state, ChatMessage::Backlog { from: host_key().public(), messages: vec![] });
+        assert!(state.caught_up);
+    }
+
+    #[test]
+    fn host_addr_only_from_the_host() {
+        let mut shared = shared_state(bob());
+        shared.discovery = Some(StaticProvider::new());
+        let mut state = ReceiveState::default();
+        let relay_url: RelayUrl = "https://relay.example.com".parse().unwrap();
+        handle(&shared, &mut state, ChatMessage::HostAddr { from: alice(), addr: NodeAddr::new(alice()).with_relay_url(relay_url.clone()) });
+        let discovery = shared.discovery.as_ref().unwrap();
+        assert!(discovery.get_node_info(alice()).is_none());
+        let host = host_key().public();
+        handle(&shared, &mut state, ChatMessage::HostAddr { from: host, addr: NodeAddr::new(host).with_relay_url(relay_url) });
+        assert!(discovery.get_node_info(host).is_some());
+    }
+
+    #[test]
+    fn game_request_and_cancel() {
+        let shared = shared_state(bob());
+        let mut state = ReceiveState::default();
+        handle(&shared, &mut state, ChatMessage::GameRequest { from: alice(), config: GameConfig::default() });
+        assert_eq!(*shared.game_request_tracker.lock().unwrap(), Some((alice(), GameConfig::default())));
+        // only the one waiting can take it back
+        handle(&shared, &mut state, ChatMessage::GameCancel { from: host_key().public() });
+        assert!(shared.game_request_tracker.lock().unwrap().is_some());
+        handle(&shared, &mut state, ChatMessage::GameCancel { from: alice() });
+        assert!(shared.game_request_tracker.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn game_start_and_over() {
+        // someone else's game, so nothing gets started here
+        let shared = shared_state(host_key().public());
+        let mut state = ReceiveState::default();
+        handle(&shared, &mut state, ChatMessage::GameRequest { from: alice(), config: GameConfig::default() });
+        handle(&shared, &mut state, ChatMessage::GameStart { from: bob(), orig_sender: alice(), game_id: 12.5, config: GameConfig::default() });
+        assert!(shared.game_request_tracker.lock().unwrap().is_none());
+        assert_eq!(shared.active_games.lock().unwrap()[&12].player_ids, [alice(), bob()]);
+        handle(&shared, &mut state, ChatMessage::GameOver { from: bob(), game_id: 12.5 });
+        assert!(shared.active_games.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn kick_only_from_the_host() {
+        let shared = shared_state(bob());
+        let mut state = ReceiveState::default();
+        let alice_key = SecretKey::from_bytes(&[1; 32]);
+        let kick = |from, target| MinimalMessage::new(MinimalMessageType::Chat(ChatMessage::Kick { from, target }));
+        // anyone else asking is ignored
+        assert!(receive(&kick(alice(), alice()).to_signed(&alice_key), &shared, &mut state));
+        assert!(!state.kicked.contains(&alice()));
+        // and nobody else can claim to be the host, the envelope says who signed it
+        let (signer, forged) = MinimalMessage::from_signed(&kick(host_key().public(), alice()).to_signed(&alice_key)).unwrap();
+        assert!(!forged.sent_by(signer));
+        assert!(receive(&kick(host_key().public(), alice()).to_signed(&host_key()), &shared, &mut state));
+        assert!(state.kicked.contains(&alice()));
+        // nothing more from them gets through
+        say(&shared, &mut state, alice(), "still here");
+        assert!(shared.scrollback.lock().unwrap().is_empty());
+        // and being kicked ourselves is what the subscribe loop leaves over
+        assert!(!receive(&kick(host_key().public(), bob()).to_signed(&host_key()), &shared, &mut state));
+    }
+
+    #[test]
+    fn backlog_only_from_the_host_and_only_once() {
+        let shared = shared_state(bob());
+        let mut state = ReceiveState::default();
+        let entry = BacklogEntry { from: alice(), name: "alice".to_string(), text: "earlier".to_string(), at: 0 };
+        handle(&shared, &mut state, ChatMessage::Backlog { from: alice(), messages: vec![entry.clone()] });
+        assert!(!state.caught_up);
+        handle(&shared, &mut state, ChatMessage::Backlog { from: host_key().public(), messages: vec![entry] });
+        assert!(state.caught_up);
+        // it's only shown, /history is for what was said since we got here
+        assert!(shared.scrollback.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn room_topic_only_from_the_host() {
+        let shared = shared_state(bob());
+        let mut state = ReceiveState::default();
+        handle(&shared, &mut state, ChatMessage::RoomTopic { from: alice(), text: "spam".to_string() });
+        assert!(shared.topic_line.lock().unwrap().is_none());
+        handle(&shared, &mut state, ChatMessage::RoomTopic { from: host_key().public(), text: "games\nonly".to_string() });
+        assert_eq!(shared.topic_line.lock().unwrap().as_deref(), Some("gamesonly"));
+    }
+
+    #[test]
+    fn attachment_changes_nothing() {
+        let shared = shared_state(bob());
+        let mut state = ReceiveState::default();
+        assert!(!handle(&shared, &mut state, ChatMessage::Attachment { from: alice(), name: "a.txt".to_string(), hash: [4; 32] }));
+        assert!(shared.scrollback.lock().unwrap().is_empty());
+        assert!(shared.last_message.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn edit_and_delete_only_your_own() {
+        let shared = shared_state(host_key().public());
+        let mut state = ReceiveState::default();
+        let nonce = say(&shared, &mut state, alice(), "helo");
+        handle(&shared, &mut state, ChatMessage::Edit { from: bob(), target_nonce: nonce, new_text: "bob was here".to_string() });
+        assert_eq!(state.recent_messages[&nonce].text, "helo");
+        handle(&shared, &mut state, ChatMessage::Edit { from: alice(), target_nonce: nonce, new_text: "hello".to_string() });
+        assert_eq!(state.recent_messages[&nonce].text, "hello");
+        handle(&shared, &mut state, ChatMessage::Delete { from: bob(), target_nonce: nonce });
+        assert!(state.recent_messages.contains_key(&nonce));
+        handle(&shared, &mut state, ChatMessage::Delete { from: alice(), target_nonce: nonce });
+        assert!(!state.recent_messages.contains_key(&nonce));
+        assert!(state.recent_order.is_empty());
+    }
+
+    #[test]
+    fn reactions_are_counted() {
+        let shared = shared_state(host_key().public());
+        let mut state = ReceiveState::default();
+        let nonce = say(&shared, &mut state, alice(), "gg");
+        handle(&shared, &mut state, ChatMessage::Reaction { from: bob(), target_nonce: nonce, emoji: "👍".to_string() });
+        handle(&shared, &mut state, ChatMessage::Reaction { from: bob(), target_nonce: nonce, emoji: "👍".to_string() });
+        // anything that isn't one of `REACTIONS` is dropped
+        handle(&shared, &mut state, ChatMessage::Reaction { from: bob(), target_nonce: nonce, emoji: "lol".to_string() });
+        let reactions = &state.recent_messages[&nonce].reactions;
+        assert_eq!(reactions.len(), 1);
+        assert_eq!(reactions["👍"], 2);
+    }
+
+    #[test]
+    fn first_name_joins_and_second_renames() {
+        // the `--log` transcript gets the same lines as the terminal, without any styling
+        let path = temp_path("names.log");
+        let mut shared = shared_state(bob());
+        shared.transcript = Some(Arc::new(Mutex::new(fs::File::create(&path).unwrap())));
+        let mut state = ReceiveState::default();
+        handle(&shared, &mut state, ChatMessage::AboutMe { from: alice(), name: "alice".to_string() });
+        handle(&shared, &mut state, ChatMessage::AboutMe { from: alice(), name: "alice".to_string() });
+        handle(&shared, &mut state, ChatMessage::AboutMe { from: alice(), name: "al".to_string() });
+        let lines: Vec<_> = fs::read_to_string(&path).unwrap().lines().map(|line| line.split_once(" > ").unwrap().1.to_string()).collect();
+        let _ = fs::remove_file(path);
+        assert_eq!(lines, [format!("{} joined as alice", alice().fmt_short()), "alice is now known as al".to_string()]);
+    }
+
+    #[test]
+    fn windows_line_ending_stays_out_of_nicknames() {
+        assert_eq!(strip_line_ending("/nick foo\r\n"), "/nick foo");
+        assert_eq!(strip_line_ending("/nick foo\n"), "/nick foo");
+        assert_eq!(strip_line_ending("/nick foo"), "/nick foo");
+        // the way the input loop gets from a typed line to the name /nick asks for
+        let Input::Command(command) = parse_input(strip_line_ending("/nick foo\r\n"), "/") else { panic!("/nick is a command") };
+        let arguments: Vec<_> = command.trim().split(" ").collect();
+        assert_eq!(arguments[0], "/nick");
+        assert_eq!(clean_nickname(&arguments[1..].join(" ")), "foo");
+    }
+
+    #[test]
+    fn long_names_differing_late_hash_apart() {
+        let a = format!("{MINIMAL_TOPIC_HEADER}{MINIMAL_VERSION}a room name long enough to pass 32 bytes, one");
+        let b = format!("{MINIMAL_TOPIC_HEADER}{MINIMAL_VERSION}a room name long enough to pass 32 bytes, two");
+        assert_eq!(a.as_bytes()[..32], b.as_bytes()[..32]);
+        assert_ne!(bytes_from_str(&a), bytes_from_str(&b));
+        assert_eq!(bytes_from_str(&a), bytes_from_str(&a));
+        // so they end up as different rooms with different hosts
+        assert_ne!(room_topic(" one, long enough to be past the header"), room_topic(" two, long enough to be past the header"));
+        // short ones still come out as they are, zero padded, so the default room didn't move
+        let mut short = [0u8; 32];
+        short[..5].copy_from_slice(b"hello");
+        assert_eq!(bytes_from_str("hello"), short);
+        // a multibyte character straddling byte 32 doesn't panic
+        bytes_from_str(&format!("{}é", "x".repeat(31)));
+    }
+
+    #[test]
+    fn garbage_is_an_error_not_a_panic() {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+        let mut rng = StdRng::seed_from_u64(255);
+        for len in 0..512 {
+            let mut bytes = vec![0u8; len];
+            rng.fill(bytes.as_mut_slice());
+            assert!(MinimalMessage::from_signed(&bytes).is_err());
+        }
+        let signed = MinimalMessage::new(MinimalMessageType::Chat(ChatMessage::Joined { from: alice() })).to_signed(&SecretKey::from_bytes(&[1; 32]));
+        assert!(MinimalMessage::from_signed(&signed).is_ok());
+        // cut short anywhere, or with any one byte changed, it's unreadable or its signature doesn't check out
+        for len in 0..signed.len() {
+            assert!(MinimalMessage::from_signed(&signed[..len]).is_err());
+        }
+        for i in 0..signed.len() {
+            let mut tampered = signed.clone();
+            tampered[i] ^= 1;
+            assert!(MinimalMessage::from_signed(&tampered).is_err());
+        }
+        // json that parses, just not as anything we know
+        assert!(MinimalMessage::from_signed(br#"{"from":"nobody","message":"{}","signature":""}"#).is_err());
+        assert!(MinimalMessage::from_signed(b"[]").is_err());
+    }
+
+    // the same, but the way it really arrives: over gossip and into the subscribe loop, which has to
+    // skip all of it and still show whatever comes next
+    #[tokio::test]
+    async fn garbage_doesnt_stop_the_subscribe_loop() {
+        let (host, (_host_sender, host_receiver), joiner, (joiner_sender, _joiner_receiver)) = local_room(&format!("test-garbage-{}", std::process::id())).await;
+        let hosts_client = shared_state(host.endpoint.node_id());
+        let (neighbor_count, _) = tokio::sync::watch::channel(0);
+        let listening = tokio::spawn(subscribe_loop(host_receiver, hosts_client.clone(), neighbor_count));
+        let alice_key = SecretKey::from_bytes(&[1; 32]);
+        let chat = |from, text: &str| MinimalMessage::new(MinimalMessageType::Chat(ChatMessage::Message { from, text: text.to_string(), sent_at: 0 }));
+        let mut tampered = chat(alice(), "tampered").to_signed(&alice_key);
+        let middle = tampered.len() / 2;
+        tampered[middle] ^= 1;
+        let spoofed = chat(bob(), "from bob, honest").to_signed(&alice_key);
+        let valid = chat(alice(), "still here").to_signed(&alice_key);
+        for bytes in [b"not even json".to_vec(), b"[]".to_vec(), tampered, spoofed, valid] {
+            joiner_sender.broadcast(bytes.into()).await.unwrap();
+        }
+        let shown = async {
+            while hosts_client.scrollback.lock().unwrap().is_empty() {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(10), shown).await.expect("the valid message never showed up");
+        assert!(!listening.is_finished());
+        let scrollback = hosts_client.scrollback.lock().unwrap().clone();
+        assert_eq!(scrollback.len(), 1);
+        assert_eq!(scrollback[0].text, "still here");
+        listening.abort();
+        joiner.router.shutdown().await.unwrap();
+        host.router.shutdown().await.unwrap();
+    }
+
+    #[test]
+    fn repeated_nonce_is_shown_once() {
+        let alice_key = SecretKey::from_bytes(&[1; 32]);
+        let shared = shared_state(bob());
+        let mut state = ReceiveState::default();
+        // gossip handing over the exact same bytes twice
+        let signed = MinimalMessage::new(MinimalMessageType::Chat(ChatMessage::Message { from: alice(), text: "once".to_string(), sent_at: 0 })).to_signed(&alice_key);
+        assert!(receive(&signed, &shared, &mut state));
+        assert!(!receive(&signed, &shared, &mut state));
+        assert_eq!(shared.scrollback.lock().unwrap().len(), 1);
+        // the cache forgets the oldest once it's full, and nothing else
+        let nonce = |i: usize| {
+            let mut nonce = [0xff; 16];
+            nonce[..8].copy_from_slice(&(i as u64).to_le_bytes());
+            nonce
+        };
+        for i in 0..SEEN_NONCES {
+            assert!(state.first_sighting(nonce(i)));
+        }
+        assert_eq!(state.seen.len(), SEEN_NONCES);
+        assert!(!state.first_sighting(nonce(SEEN_NONCES - 1)));
+        let (_, message) = MinimalMessage::from_signed(&signed).unwrap();
+        assert!(state.first_sighting(message.nonce));
+    }
+
+    #[test]
+    fn sanitize_strips_escapes_and_controls() {
+        assert_eq!(sanitize("\x1b[31mred\x1b[0m"), "red");
+        assert_eq!(sanitize("\x1b[2J\x1b[1;1Hcleared"), "cleared");
+        // OSC, ended by BEL or by ESC \
+        assert_eq!(sanitize("\x1b]0;pwned\x07hi"), "hi");
+        assert_eq!(sanitize("\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x1b\\"), "link");
+        // an unterminated one takes the rest of the line with it
+        assert_eq!(sanitize("ok\x1b]0;never ends"), "ok");
+        // two character escapes, like a full reset
+        assert_eq!(sanitize("\x1bcreset"), "reset");
+        assert_eq!(sanitize("trailing\x1b"), "trailing");
+        // no newlines to start a fake "> " status line with, or anything else below a space
+        assert_eq!(sanitize("hi\n> you were kicked"), "hi> you were kicked");
+        assert_eq!(sanitize("a\rb\tc\x08d\x07e\u{9b}f"), "abcdef");
+        // everything printable stays as it is
+        assert_eq!(sanitize("héllo wörld 👍 [brackets] ~"), "héllo wörld 👍 [brackets] ~");
+    }
+}
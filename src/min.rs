@@ -1,11 +1,99 @@
-use std::{io::{stdout, Write}, hash::Hash};
+use std::{fmt, io::Write, hash::Hash};
 use anyhow::Result;
-use crossterm::{cursor::{MoveTo, MoveToNextLine}, execute, style::{StyledContent, Stylize}, terminal::{Clear, ClearType}};
-use rand::random_range;
+use crossterm::{cursor::MoveTo, execute, style::{StyledContent, Stylize}, terminal::{Clear, ClearType}};
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use hashbag::HashBag;
+use serde::{Deserialize, Serialize};
 
-fn within_range(ry1: u16, ry2: u16, ro: u16, rx: u16, cy: u16, cx: u16) -> bool {
-  cx == rx && (cy >= ry1 + ro) && (cy <= ry2 + ro)
+// where things go on screen. every coordinate here (and everything `ui` draws or hit-tests) is a cell
+// counted from the frame's top left corner: (0, 0) is the corner of the border, so the first usable
+// cell inside the frame is (1, 1). nothing adds its own +1 for the border, that is already baked into
+// these numbers. when the frame fills the terminal these are the same cells crossterm hands us in mouse
+// events, otherwise see `Frame::local`
+const COLOR_SLOTS: (u16, u16) = (11, 1); // (col, row) of the first color slot
+const COLOR_SLOT_WIDTH: u16 = 3; // narrow enough that 8 colors fit before the hover panel
+const SKILL_SLOTS: (u16, u16) = (11, 2);
+const SKILL_SLOT_WIDTH: u16 = 9;
+const REFUND_BUTTON: (u16, u16) = (2, 2);
+const REFUND_BUTTON_WIDTH: u16 = 6;
+const HOVER_PANEL: (u16, u16) = (40, 1); // the name and description of whatever is under the cursor
+const HOVER_PANEL_WIDTH: usize = 18;
+const OPPONENT_PANEL: (u16, u16) = (40, 5); // under the hover panel, how the other board is doing
+// how many slots fit on each vbox row before running into the hover panel
+pub const MAX_COLORS: usize = ((HOVER_PANEL.0 - COLOR_SLOTS.0) / COLOR_SLOT_WIDTH) as usize;
+pub const MAX_SKILLS: usize = ((HOVER_PANEL.0 - SKILL_SLOTS.0) / SKILL_SLOT_WIDTH) as usize;
+
+// the part of the terminal the game is drawn in. by default that's all of it, but on a huge terminal
+// the board is easier to read in a smaller frame in the middle than stretched out to the edges
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame {
+  // the terminal cell the frame's top left corner is on
+  col: u16,
+  row: u16,
+  cols: u16,
+  rows: u16,
+}
+impl Frame {
+  // the whole terminal, or at most `max_cols` x `max_rows` of it in the middle. `None` fills that way
+  pub fn centered(term_cols: u16, term_rows: u16, max_cols: Option<u16>, max_rows: Option<u16>) -> Self {
+    let cols = max_cols.map_or(term_cols, |max| max.min(term_cols));
+    let rows = max_rows.map_or(term_rows, |max| max.min(term_rows));
+    Frame { col: (term_cols - cols) / 2, row: (term_rows - rows) / 2, cols, rows }
+  }
+  // a terminal cell (like a mouse position) in the frame's own cells. anything left of or above the
+  // frame lands on its border, where there's nothing to hit
+  pub fn local(&self, term_col: u16, term_row: u16) -> (u16, u16) {
+    (term_col.saturating_sub(self.col), term_row.saturating_sub(self.row))
+  }
+  // where to move the terminal cursor to get to (`col`, `row`) in the frame
+  fn at(&self, col: u16, row: u16) -> MoveTo {
+    MoveTo(self.col + col, self.row + row)
+  }
+}
+
+// clear the screen and draw the border, with `title` in the top edge. a title too long for the
+// frame gets cut short rather than pushing the corner off the edge. everything that draws takes `out`
+// instead of going straight to stdout, so a frame can be drawn into a buffer and looked at
+fn draw_frame(out: &mut impl Write, title: &str, frame: Frame) -> Result<()> {
+  // "┌ " and " ┐" around the title take 4 cells
+  let space = usize::from(frame.cols.saturating_sub(4));
+  let title: String = title.chars().take(space).collect();
+  let dashes = space - title.chars().count();
+  execute!(out, Clear(ClearType::All), frame.at(0, 0))?;
+  write!(out, "┌ {title} {}┐", "─".repeat(dashes))?;
+  for row in 1..frame.rows.saturating_sub(1) {
+    execute!(out, frame.at(0, row))?;
+    write!(out, "│{}│", " ".repeat(frame.cols.saturating_sub(2).into()))?;
+  }
+  execute!(out, frame.at(0, frame.rows.saturating_sub(1)))?;
+  write!(out, "└{}┘", "─".repeat(frame.cols.saturating_sub(2).into()))?;
+  Ok(())
+}
+
+// what `?` shows in place of the board
+const CONTROLS: &[(&str, &str)] = &[
+  ("mouse / arrows", "move around"),
+  ("click / space", "take a component"),
+  ("enter", "craft what you're holding"),
+  ("refund", "put everything held back"),
+  ("s", "surrender, counts as a loss"),
+  ("q", "quit, nobody wins"),
+  ("? / h", "close this help"),
+];
+
+// the controls, drawn in their own frame over the whole board. whatever gets drawn next clears it all
+pub fn help_ui(out: &mut impl Write, frame: Frame) -> Result<()> {
+  draw_frame(out, "minimal · help", frame)?;
+  for (row, (keys, action)) in CONTROLS.iter().enumerate().take(frame.rows.saturating_sub(2).into()) {
+    execute!(out, frame.at(2, 1 + row as u16))?;
+    write!(out, "{} {}", format!("{keys:<16}").bold(), action)?;
+  }
+  Ok(())
+}
+
+// is the cursor on `row`, somewhere in the `width` cells starting at `col`?
+fn within_range(col: u16, width: u16, row: u16, cursor_col: u16, cursor_row: u16) -> bool {
+  cursor_row == row && cursor_col >= col && cursor_col < col + width
 }
 fn make_hashbag<T: IntoIterator>(items: T) -> HashBag<T::Item>
   where T::Item: Hash + Eq {
@@ -14,7 +102,7 @@ fn make_hashbag<T: IntoIterator>(items: T) -> HashBag<T::Item>
   bag
 }
 
-#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 enum Component {
   Red,
   Green,
@@ -25,9 +113,9 @@ enum Component {
   Debuff,
   Stun
 }
-impl Component {
-  fn to_string(&self) -> String {
-    match *self {
+impl fmt::Display for Component {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str(match *self {
       Self::Red => "Red",
       Self::Green => "Green",
       Self::Blue => "Blue",
@@ -36,8 +124,10 @@ impl Component {
       Self::Buff => "Buff",
       Self::Debuff => "Debuff",
       Self::Stun => "Stun",
-    }.to_string()
+    })
   }
+}
+impl Component {
   fn get_description(&self) -> String {
     match self {
       Self::Red => "Fast speed, physical type. Chaos and momentum.".to_string(),
@@ -60,13 +150,13 @@ impl Component {
   fn is_color(&self) -> bool {
     *self == Self::Red || *self == Self::Green || *self == Self::Blue
   }
-  fn random_color() -> Self {
-    let colors = vec![Self::Red, Self::Green, Self::Blue];
-    colors[random_range(0..colors.len())].clone()
+  fn random_color(rng: &mut impl Rng) -> Self {
+    let colors = [Self::Red, Self::Green, Self::Blue];
+    colors[rng.random_range(0..colors.len())].clone()
   }
-  fn random_skill() -> Self {
-    let skills = vec![Self::Attack, Self::Block, Self::Buff, Self::Debuff, Self::Stun];
-    skills[random_range(0..skills.len())].clone()
+  fn random_skill(rng: &mut impl Rng) -> Self {
+    let skills = [Self::Attack, Self::Block, Self::Buff, Self::Debuff, Self::Stun];
+    skills[rng.random_range(0..skills.len())].clone()
   }
 }
 struct Skill {
@@ -90,82 +180,450 @@ impl Skill {
     ]
   }
   fn craft(components: &HashBag<Component>) -> Option<Self> {
-    for i in Self::get_all_recipes() {
-      if *components == i.components { return Some(i); }
-    }
-    None
+    Self::get_all_recipes().into_iter().find(|i| *components == i.components)
+  }
+}
+// the knobs that shape a game. both players have to build their state from the same config
+// (and the same seed), otherwise they'd be looking at different boards
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameConfig {
+  pub starting_bits: i32,
+  pub colors: usize,
+  pub skills: usize
+}
+impl Default for GameConfig {
+  fn default() -> Self {
+    GameConfig { starting_bits: 40, colors: 6, skills: 3 }
+  }
+}
+impl GameConfig {
+  // whether `ui` has room for a vbox this big. a config from someone else gets checked before it's used
+  pub fn fits_layout(&self) -> bool {
+    self.colors <= MAX_COLORS && self.skills <= MAX_SKILLS
   }
 }
+// a whole board in a form that can be sent to someone else and turned back into a `MinimalGameState`.
+// skills go by name since their recipes are the same everywhere, and the bag of held components is
+// just a list
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameSnapshot {
+  vbox: Vec<Component>,
+  bits: i32,
+  held: Vec<Component>,
+  skills: Vec<String>
+}
+// everything a player can do to their board. these are what get sent to the opponent, who plays them
+// on their own copy of our board, so both copies stay the same as long as they're played in order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Move {
+  // the vbox slot, see `slot_at`
+  Take(usize),
+  Refund,
+  Craft,
+}
 pub struct MinimalGameState {
   vbox: Vec<Component>,
-  bits: i32
+  bits: i32,
+  // components taken out of the vbox but not crafted yet, their cost is already taken from `bits`
+  held: HashBag<Component>,
+  skills: Vec<Skill>,
+  // shown in the top of the border, "minimal" unless `set_title` says otherwise
+  title: String
 }
 
 impl MinimalGameState {
-  pub fn new() -> Self {
+  pub fn new(config: &GameConfig, seed: u64) -> Self {
     // create a new vbox and add random colors and skills to it
+    // the rng is seeded so that both players roll the exact same vbox
+    let mut rng = StdRng::seed_from_u64(seed);
     let mut vbox = vec![];
-    for _i in 0..6 {
-      vbox.push(Component::random_color());
-    }
-    for _i in 0..3 {
-      vbox.push(Component::random_skill());
-    }
-    let bits = 40;
-    MinimalGameState { vbox, bits }
-  }
-  pub fn ui(&self, term_cols: u16, term_rows: u16, cursor_col: u16, cursor_row: u16) -> Result<()> {
-    let mut stdout = stdout();
-    // draw the minimal border
-    execute!(stdout, Clear(ClearType::All), MoveTo(0, 0))?;
-    write!(stdout, "┌ minimal {}┐", "─".repeat((term_cols - 11).into()))?;
-    for _i in 1..(term_rows-1) {
-        execute!(stdout, MoveToNextLine(1))?;
-        write!(stdout, "│{}│", " ".repeat((term_cols - 2).into()))?;
-    }
-    execute!(stdout, MoveTo(0, term_rows-1))?;
-    write!(stdout, "└{}┘", "─".repeat((term_cols - 2).into()))?;
+    for _i in 0..config.colors {
+      vbox.push(Component::random_color(&mut rng));
+    }
+    for _i in 0..config.skills {
+      vbox.push(Component::random_skill(&mut rng));
+    }
+    // slots are hit-tested and sent to the opponent by index, so the order has to be the same everywhere.
+    // sorting puts colors before skills, each in the order `Component` lists them
+    vbox.sort();
+    let bits = config.starting_bits;
+    MinimalGameState { vbox, bits, held: HashBag::new(), skills: vec![], title: "minimal".to_string() }
+  }
+  pub fn snapshot(&self) -> GameSnapshot {
+    let mut held: Vec<_> = self.held.iter().cloned().collect();
+    held.sort();
+    GameSnapshot { vbox: self.vbox.clone(), bits: self.bits, held, skills: self.skill_names() }
+  }
+  // None if the snapshot has a skill we don't know the recipe for
+  pub fn from_snapshot(snapshot: GameSnapshot) -> Option<Self> {
+    let skills = snapshot.skills.iter()
+      .map(|name| Skill::get_all_recipes().into_iter().find(|skill| skill.name == *name))
+      .collect::<Option<Vec<_>>>()?;
+    Some(MinimalGameState {
+      vbox: snapshot.vbox,
+      bits: snapshot.bits,
+      held: make_hashbag(snapshot.held),
+      skills,
+      title: "minimal".to_string()
+    })
+  }
+  pub fn set_title(&mut self, title: &str) {
+    self.title = title.to_string();
+  }
+  // find which vbox slot (if any) is under the cursor, using the same layout that `ui` draws
+  pub fn slot_at(&self, cursor_col: u16, cursor_row: u16) -> Option<usize> {
+    let (mut colors, mut skills) = (0, 0);
+    for (index, component) in self.vbox.iter().enumerate() {
+      let hit = if component.is_color() {
+        colors += 1;
+        within_range(COLOR_SLOTS.0 + (colors - 1) * COLOR_SLOT_WIDTH, COLOR_SLOT_WIDTH, COLOR_SLOTS.1, cursor_col, cursor_row)
+      } else {
+        skills += 1;
+        within_range(SKILL_SLOTS.0 + (skills - 1) * SKILL_SLOT_WIDTH, SKILL_SLOT_WIDTH, SKILL_SLOTS.1, cursor_col, cursor_row)
+      };
+      if hit { return Some(index); }
+    }
+    None
+  }
+  fn on_refund_button(cursor_col: u16, cursor_row: u16) -> bool {
+    within_range(REFUND_BUTTON.0, REFUND_BUTTON_WIDTH, REFUND_BUTTON.1, cursor_col, cursor_row)
+  }
+  // move a component from the vbox into the held bag, paying for it up front. false if it can't be afforded
+  fn hold(&mut self, index: usize) -> bool {
+    let cost = self.vbox[index].get_cost();
+    if self.bits < cost { return false; }
+    self.bits -= cost;
+    self.held.insert(self.vbox.remove(index));
+    true
+  }
+  // put everything held back into the vbox and give the bits back
+  pub fn refund(&mut self) {
+    for (component, count) in self.held.drain() {
+      for _i in 0..count {
+        self.bits += component.get_cost();
+        self.vbox.push(component.clone());
+      }
+    }
+    // the bag hands things back in whatever order it likes, which won't match the opponent's
+    self.vbox.sort();
+  }
+  // turn the held components into a skill, if they make one. otherwise nothing changes
+  pub fn craft_held(&mut self) -> bool {
+    match Skill::craft(&self.held) {
+      Some(skill) => {
+        self.skills.push(skill);
+        self.held.clear();
+        true
+      }
+      None => false
+    }
+  }
+  pub fn skill_names(&self) -> Vec<String> {
+    self.skills.iter().map(|skill| skill.name.clone()).collect()
+  }
+  // returns what the click did, if anything, so it can be sent to the opponent
+  pub fn click(&mut self, cursor_col: u16, cursor_row: u16) -> Option<Move> {
+    if Self::on_refund_button(cursor_col, cursor_row) {
+      // nothing held means nothing to tell anyone
+      if self.held.is_empty() { return None; }
+      self.refund();
+      return Some(Move::Refund);
+    } else if let Some(index) = self.slot_at(cursor_col, cursor_row) && self.hold(index) {
+      return Some(Move::Take(index));
+    }
+    None
+  }
+  // play a move someone else made on their board. it comes off the network, so a slot that isn't
+  // there is refused instead of trusted. false if nothing changed
+  pub fn apply(&mut self, played: Move) -> bool {
+    match played {
+      Move::Take(index) => index < self.vbox.len() && self.hold(index),
+      Move::Refund => {
+        let changed = !self.held.is_empty();
+        self.refund();
+        changed
+      }
+      Move::Craft => self.craft_held(),
+    }
+  }
+  // whether there's nothing left worth doing on this board. every recipe is a single skill component
+  // for now, so that's whether one could still be paid for, counting the bits refunding would give back
+  pub fn is_finished(&self) -> bool {
+    let refundable: i32 = self.held.iter().map(Component::get_cost).sum();
+    Skill::craft(&self.held).is_none()
+      && !self.vbox.iter().any(|component| !component.is_color() && component.get_cost() <= self.bits + refundable)
+  }
+  fn can_afford(&self, component: &Component) -> bool {
+    self.bits >= component.get_cost()
+  }
+  // how a vbox slot looks, shared by the color and skill rows. something we can't pay for right now is
+  // dimmed and crossed out rather than hidden, it comes back as soon as there are bits for it.
+  // (taken components leave the vbox entirely, so there's no used-up state to draw here yet)
+  fn slot_style(&self, component: &Component, hovered: bool) -> StyledContent<String> {
+    if !self.can_afford(component) {
+      component.stylize().dim().crossed_out()
+    } else if hovered {
+      component.stylize().bold()
+    } else {
+      component.stylize()
+    }
+  }
+  // the cursor is in the frame's cells, see `Frame::local`
+  pub fn ui(&self, out: &mut impl Write, frame: Frame, cursor_col: u16, cursor_row: u16) -> Result<()> {
+    draw_frame(out, &self.title, frame)?;
     let mut hovered_name = "".to_string();
     let mut hovered_desc = "".to_string();
+    let hovered_slot = self.slot_at(cursor_col, cursor_row);
     // draw the VBOX's colors!!
-    for (i, component) in self.vbox.iter().filter(|c| c.is_color()).enumerate() {
+    for (i, (index, component)) in self.vbox.iter().enumerate().filter(|(_, c)| c.is_color()).enumerate() {
       let ii = i as u16;
-      execute!(stdout, MoveTo(11 + ii * 4, 1))?;
-      write!(stdout, "{}", if self.bits < component.get_cost() { component.stylize().crossed_out() } else {
-        if within_range(11, 14, ii * 4, 1, cursor_col, cursor_row) {
-          hovered_name = component.to_string();
-          hovered_desc = component.get_description();
-          component.stylize().bold()
-        } else { component.stylize() }
-      })?;
+      execute!(out, frame.at(COLOR_SLOTS.0 + ii * COLOR_SLOT_WIDTH, COLOR_SLOTS.1))?;
+      let hovered = hovered_slot == Some(index) && self.can_afford(component);
+      if hovered {
+        hovered_name = component.to_string();
+        hovered_desc = component.get_description();
+      }
+      write!(out, "{}", self.slot_style(component, hovered))?;
     }
     // and draw the skills too
-    for (i, component) in self.vbox.iter().filter(|c| !c.is_color()).enumerate() {
+    for (i, (index, component)) in self.vbox.iter().enumerate().filter(|(_, c)| !c.is_color()).enumerate() {
       let ii = i as u16;
-      execute!(stdout, MoveTo(11 + ii * 9, 2))?;
-      write!(stdout, "{}", if self.bits < component.get_cost() { component.stylize().crossed_out() } else {
-        if within_range(11, 19, ii * 9, 2, cursor_col, cursor_row) {
-          hovered_name = component.to_string();
-          hovered_desc = component.get_description();
-          component.stylize().bold()
-        } else { component.stylize() }
-      })?;
+      execute!(out, frame.at(SKILL_SLOTS.0 + ii * SKILL_SLOT_WIDTH, SKILL_SLOTS.1))?;
+      let hovered = hovered_slot == Some(index) && self.can_afford(component);
+      if hovered {
+        hovered_name = component.to_string();
+        hovered_desc = component.get_description();
+      }
+      write!(out, "{}", self.slot_style(component, hovered))?;
     }
     // draw the hovered item's description
-    execute!(stdout, MoveTo(40, 1))?;
-    write!(stdout, "{}", hovered_name.bold())?;
+    execute!(out, frame.at(HOVER_PANEL.0, HOVER_PANEL.1))?;
+    write!(out, "{}", hovered_name.bold())?;
     for i in 0..3 {
       // let's just assume it won't be more than like 3 lines long
-      execute!(stdout, MoveTo(40, 2 + i))?;
+      execute!(out, frame.at(HOVER_PANEL.0, HOVER_PANEL.1 + 1 + i))?;
       // get the relevant part of the string and print it
-      if(hovered_desc.len() > 18) { write!(stdout, "{}", hovered_desc.drain(..18).collect::<String>())?; }
-      else { write!(stdout, "{}", hovered_desc)?; }
+      if hovered_desc.len() > HOVER_PANEL_WIDTH { write!(out, "{}", hovered_desc.drain(..HOVER_PANEL_WIDTH).collect::<String>())?; }
+      else { write!(out, "{}", hovered_desc)?; }
     }
     // draw the current money and the refund button
-    execute!(stdout, MoveTo(2, 1))?;
-    write!(stdout, "{}B", self.bits)?;
-    execute!(stdout, MoveTo(2, 2))?;
-    write!(stdout, "{}", "refund".dark_grey())?; // todo: color this based on whether something refundable is being held
+    execute!(out, frame.at(2, 1))?;
+    write!(out, "{}B", self.bits)?;
+    execute!(out, frame.at(REFUND_BUTTON.0, REFUND_BUTTON.1))?;
+    write!(out, "{}", if self.held.is_empty() { "refund".dark_grey() } else { "refund".white() })?;
+    // draw what is being held and what it would craft into, so people can experiment before committing
+    execute!(out, frame.at(2, 3))?;
+    let mut held: Vec<_> = self.held.iter().collect();
+    held.sort(); // the bag has no order of its own, keep it from jumping around between redraws
+    write!(out, "held:")?;
+    for component in held {
+      write!(out, " {}", component.stylize())?;
+    }
+    execute!(out, frame.at(2, 4))?;
+    if !self.held.is_empty() {
+      match Skill::craft(&self.held) {
+        Some(skill) => write!(out, "-> {} {}", skill.name.bold(), "(enter to craft)".dark_grey())?,
+        None => write!(out, "-> {}", "no valid skill".dark_grey())?,
+      }
+    }
+    execute!(out, frame.at(2, 5))?;
+    write!(out, "skills: {}", self.skills.iter().map(|skill| skill.name.as_str()).collect::<Vec<_>>().join(", "))?;
     Ok(())
   }
-}
\ No newline at end of file
+  // one line about this board, for showing the opponent's next to ours. drawn after `ui`, which clears the screen
+  pub fn summary_ui(&self, out: &mut impl Write, frame: Frame, who: &str) -> Result<()> {
+    execute!(out, frame.at(OPPONENT_PANEL.0, OPPONENT_PANEL.1))?;
+    let summary = format!("{who}: {}B {} skills", self.bits, self.skills.len());
+    write!(out, "{}", summary.chars().take(HOVER_PANEL_WIDTH).collect::<String>().dark_grey())?;
+    Ok(())
+  }
+}
+// one move for the solo bot. kept apart from everything else so it can get smarter later, right now
+// it just grabs the first skill component it can pay for and crafts it straight away, and saves its
+// bits otherwise since colors don't craft into anything yet. false once there's nothing left to do
+pub fn bot_turn(state: &mut MinimalGameState) -> bool {
+  let Some(index) = state.vbox.iter().position(|component| !component.is_color() && state.can_afford(component)) else {
+    return false;
+  };
+  state.hold(index) && state.craft_held()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn custom_config_shapes_the_board() {
+    let config = GameConfig { starting_bits: 7, colors: 2, skills: 5 };
+    let state = MinimalGameState::new(&config, 1);
+    assert_eq!(state.bits, 7);
+    assert_eq!(state.vbox.iter().filter(|c| c.is_color()).count(), 2);
+    assert_eq!(state.vbox.iter().filter(|c| !c.is_color()).count(), 5);
+    assert!(state.held.is_empty());
+    assert!(state.skills.is_empty());
+  }
+
+  // the player plays the bot's strategy too, so the whole game runs without anyone clicking
+  fn play_bot_game(seed: u64) -> (Vec<String>, Vec<String>) {
+    let config = GameConfig::default();
+    let (mut ours, mut bot) = (MinimalGameState::new(&config, seed), MinimalGameState::new(&config, seed));
+    let mut turns = 0;
+    while !(ours.is_finished() && bot.is_finished()) {
+      let moved = bot_turn(&mut ours) | bot_turn(&mut bot);
+      assert!(moved, "neither board could move, but the game wasn't over");
+      turns += 1;
+      assert!(turns <= config.skills, "the game should be over once every skill is crafted");
+    }
+    (ours.skill_names(), bot.skill_names())
+  }
+
+  #[test]
+  fn bot_game_plays_to_the_end() {
+    let (ours, bot) = play_bot_game(42);
+    // 40 bits covers all 3 skills, and the same seed means the same vbox on both sides
+    assert_eq!(ours.len(), 3);
+    assert_eq!(ours, bot);
+    assert_eq!(play_bot_game(42), (ours, bot));
+  }
+
+  #[test]
+  fn within_range_edges() {
+    assert!(within_range(5, 3, 2, 5, 2));
+    assert!(within_range(5, 3, 2, 7, 2));
+    assert!(!within_range(5, 3, 2, 8, 2));
+    assert!(!within_range(5, 3, 2, 4, 2));
+    assert!(!within_range(5, 3, 2, 5, 1));
+  }
+
+  #[test]
+  fn slot_at_edges() {
+    // sorted, so the colors are slots 0..3 and the skills 3..5
+    let state = MinimalGameState::new(&GameConfig { starting_bits: 40, colors: 3, skills: 2 }, 1);
+    let last_color = COLOR_SLOTS.0 + 3 * COLOR_SLOT_WIDTH - 1;
+    assert_eq!(state.slot_at(COLOR_SLOTS.0 - 1, COLOR_SLOTS.1), None);
+    assert_eq!(state.slot_at(COLOR_SLOTS.0, COLOR_SLOTS.1), Some(0));
+    assert_eq!(state.slot_at(COLOR_SLOTS.0 + COLOR_SLOT_WIDTH, COLOR_SLOTS.1), Some(1));
+    assert_eq!(state.slot_at(last_color, COLOR_SLOTS.1), Some(2));
+    assert_eq!(state.slot_at(last_color + 1, COLOR_SLOTS.1), None);
+    let last_skill = SKILL_SLOTS.0 + 2 * SKILL_SLOT_WIDTH - 1;
+    assert_eq!(state.slot_at(SKILL_SLOTS.0, SKILL_SLOTS.1), Some(3));
+    assert_eq!(state.slot_at(last_skill, SKILL_SLOTS.1), Some(4));
+    assert_eq!(state.slot_at(last_skill + 1, SKILL_SLOTS.1), None);
+    // the border is never a slot
+    assert_eq!(state.slot_at(0, 0), None);
+  }
+
+  #[test]
+  fn vbox_is_sorted() {
+    let config = GameConfig { starting_bits: 40, colors: 7, skills: 4 };
+    let state = MinimalGameState::new(&config, 9);
+    assert!(state.vbox.is_sorted());
+    // colors first, so the slot order matches the two rows `ui` draws
+    let first_skill = state.vbox.iter().position(|component| !component.is_color()).unwrap();
+    assert!(state.vbox[..first_skill].iter().all(Component::is_color));
+    assert!(!state.vbox[first_skill..].iter().any(Component::is_color));
+    assert_eq!(state.vbox, MinimalGameState::new(&config, 9).vbox);
+    // taking things out and putting them back doesn't shuffle anything
+    let mut played = MinimalGameState::new(&config, 9);
+    assert!(played.apply(Move::Take(first_skill)));
+    assert!(played.apply(Move::Take(0)));
+    assert!(played.apply(Move::Refund));
+    assert_eq!(played.vbox, state.vbox);
+  }
+
+  fn draw_frame_to_string(title: &str, frame: Frame) -> String {
+    let mut out = vec![];
+    draw_frame(&mut out, title, frame).unwrap();
+    String::from_utf8(out).unwrap()
+  }
+
+  #[test]
+  fn long_title_is_cut_short() {
+    // 10 cells across leaves 6 for the title between "┌ " and " ┐"
+    let drawn = draw_frame_to_string("minimal · a very long room name", Frame::centered(10, 4, None, None));
+    assert!(drawn.contains("┌ minima ┐"));
+    assert!(drawn.contains("└────────┘"));
+    // one that fits gets dashes after it instead
+    assert!(draw_frame_to_string("min", Frame::centered(10, 4, None, None)).contains("┌ min ───┐"));
+    // too narrow for any of the title, without the width math underflowing
+    assert!(!draw_frame_to_string("minimal", Frame::centered(3, 2, None, None)).contains('m'));
+  }
+
+  #[test]
+  fn snapshot_round_trip() {
+    // something crafted and something still held, so every field has something in it
+    let mut state = MinimalGameState::new(&GameConfig::default(), 3);
+    let first_skill = state.vbox.iter().position(|component| !component.is_color()).unwrap();
+    assert!(state.apply(Move::Take(first_skill)));
+    assert!(state.apply(Move::Craft));
+    assert!(state.apply(Move::Take(0)));
+    let snapshot = state.snapshot();
+    let json = serde_json::to_string(&snapshot).unwrap();
+    assert_eq!(serde_json::from_str::<GameSnapshot>(&json).unwrap(), snapshot);
+    let bytes = postcard::to_allocvec(&snapshot).unwrap();
+    assert_eq!(postcard::from_bytes::<GameSnapshot>(&bytes).unwrap(), snapshot);
+    let rebuilt = MinimalGameState::from_snapshot(snapshot.clone()).unwrap();
+    assert_eq!(rebuilt.snapshot(), snapshot);
+    assert_eq!(rebuilt.skill_names(), state.skill_names());
+    // a skill nobody has a recipe for can't be rebuilt
+    let unknown = GameSnapshot { skills: vec!["Fireball".to_string()], ..snapshot };
+    assert!(MinimalGameState::from_snapshot(unknown).is_none());
+  }
+
+  fn ui_to_string(state: &MinimalGameState, cursor_col: u16, cursor_row: u16) -> String {
+    let mut out = vec![];
+    state.ui(&mut out, Frame::centered(60, 7, None, None), cursor_col, cursor_row).unwrap();
+    String::from_utf8(out).unwrap()
+  }
+
+  #[test]
+  fn ui_renders_into_a_buffer() {
+    let state = MinimalGameState::new(&GameConfig::default(), 5);
+    let first_skill = &state.vbox[state.vbox.iter().position(|component| !component.is_color()).unwrap()];
+    let description: String = first_skill.get_description().chars().take(HOVER_PANEL_WIDTH).collect();
+    let drawn = ui_to_string(&state, 0, 0);
+    assert!(drawn.contains("┌ minimal "));
+    assert!(drawn.contains("40B"));
+    assert!(drawn.contains("refund"));
+    assert!(drawn.contains("skills: "));
+    assert!(drawn.contains(&first_skill.to_string()));
+    // nothing under the cursor, so nothing in the hover panel
+    assert!(!drawn.contains(&description));
+    assert!(ui_to_string(&state, SKILL_SLOTS.0, SKILL_SLOTS.1).contains(&description));
+    // everything is affordable, so nothing is crossed out (SGR 9)
+    assert!(!drawn.contains("\x1b[9m"));
+  }
+
+  #[test]
+  fn ui_crosses_out_what_cant_be_afforded() {
+    // a bit is enough for a color but not a skill
+    let state = MinimalGameState::new(&GameConfig { starting_bits: 1, colors: 1, skills: 1 }, 5);
+    let drawn = ui_to_string(&state, SKILL_SLOTS.0, SKILL_SLOTS.1);
+    assert!(drawn.contains("1B"));
+    assert_eq!(drawn.matches("\x1b[9m").count(), 1);
+    // and hovering it doesn't say what it is
+    let description: String = state.vbox[1].get_description().chars().take(HOVER_PANEL_WIDTH).collect();
+    assert!(!drawn.contains(&description));
+  }
+
+  #[test]
+  fn eight_colors_and_two_skills_fit() {
+    let config = GameConfig { starting_bits: 40, colors: 8, skills: 2 };
+    assert!(config.fits_layout());
+    // the smallest terminal a game runs in
+    let (cols, rows) = (crate::MIN_TERM_COLS, crate::MIN_TERM_ROWS);
+    let state = MinimalGameState::new(&config, 11);
+    let mut out = vec![];
+    state.ui(&mut out, Frame::centered(cols, rows, None, None), 0, 0).unwrap();
+    // the last slot of each row ends before the hover panel, which ends before the right border
+    let colors_end = COLOR_SLOTS.0 + 8 * COLOR_SLOT_WIDTH;
+    let skills_end = SKILL_SLOTS.0 + 2 * SKILL_SLOT_WIDTH;
+    assert!(colors_end <= HOVER_PANEL.0 && skills_end <= HOVER_PANEL.0);
+    assert!(usize::from(HOVER_PANEL.0) + HOVER_PANEL_WIDTH < usize::from(cols - 1));
+    assert!(HOVER_PANEL.1 + 3 < rows - 1 && OPPONENT_PANEL.1 < rows - 1);
+    // and every slot can still be pointed at
+    assert_eq!(state.slot_at(colors_end - 1, COLOR_SLOTS.1), Some(7));
+    assert_eq!(state.slot_at(skills_end - 1, SKILL_SLOTS.1), Some(9));
+    assert_eq!(state.slot_at(HOVER_PANEL.0, HOVER_PANEL.1), None);
+  }
+}